@@ -0,0 +1,376 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    convert::TryFrom,
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use futures::AsyncReadExt;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use surf::Response;
+
+use crate::check::{Failure, Severity};
+
+/// Conditions a response has to meet for a check to pass
+///
+/// Every assertion is optional. Without a `status` assertion any 2xx status
+/// is accepted, as it is for monitors without assertions.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Assertions {
+    /// status codes the response may have
+    /// each is either a code (`200`), a class (`"2xx"`) or a range (`"200-299"`)
+    /// default: any 2xx status
+    pub status: Option<Vec<StatusRange>>,
+    /// text the response body has to contain
+    pub body_contains: Option<String>,
+    /// regular expression the response body has to match
+    pub body_matches: Option<Pattern>,
+    /// values expected in a JSON response body, by path
+    /// example: `{ "$.status": "ok", "checks[0].healthy": true }`
+    pub json: Option<BTreeMap<String, Value>>,
+    /// headers the response has to include
+    /// a `null` value only requires the header to be present
+    pub headers: Option<BTreeMap<String, Option<String>>>,
+    /// maximum size of the response body, in bytes
+    pub max_size: Option<u64>,
+}
+
+/// An inclusive range of status codes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "RawStatus", into = "RawStatus")]
+pub struct StatusRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawStatus {
+    Code(u16),
+    Pattern(String),
+}
+
+impl StatusRange {
+    pub fn contains(&self, status: u16) -> bool {
+        (self.start..=self.end).contains(&status)
+    }
+}
+
+impl TryFrom<RawStatus> for StatusRange {
+    type Error = String;
+
+    fn try_from(raw: RawStatus) -> Result<Self, Self::Error> {
+        let pattern = match raw {
+            RawStatus::Code(code) => {
+                return Ok(Self {
+                    start: code,
+                    end: code,
+                })
+            }
+            RawStatus::Pattern(pattern) => pattern,
+        };
+
+        let invalid = || format!("invalid status pattern {:?}", pattern);
+
+        let pattern_lower = pattern.trim().to_ascii_lowercase();
+
+        if let Some(class) = pattern_lower.strip_suffix("xx") {
+            let start = class
+                .parse::<u16>()
+                .ok()
+                .and_then(|class| class.checked_mul(100))
+                .filter(|start| *start <= u16::MAX - 99)
+                .ok_or_else(invalid)?;
+
+            return Ok(Self {
+                start,
+                end: start + 99,
+            });
+        }
+
+        let (start, end) = match pattern_lower.split_once('-') {
+            Some((start, end)) => (start.trim(), end.trim()),
+            None => (pattern_lower.as_str(), pattern_lower.as_str()),
+        };
+
+        let range = Self {
+            start: start.parse().map_err(|_| invalid())?,
+            end: end.parse().map_err(|_| invalid())?,
+        };
+
+        if range.start > range.end {
+            return Err(invalid());
+        }
+
+        Ok(range)
+    }
+}
+
+impl From<StatusRange> for RawStatus {
+    fn from(range: StatusRange) -> Self {
+        if range.start == range.end {
+            Self::Code(range.start)
+        } else {
+            Self::Pattern(range.to_string())
+        }
+    }
+}
+
+impl fmt::Display for StatusRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else if self.start.is_multiple_of(100) && self.end == self.start + 99 {
+            write!(f, "{}xx", self.start / 100)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+/// A regular expression, compiled when the configuration is parsed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pattern(pub Regex);
+
+impl TryFrom<String> for Pattern {
+    type Error = regex::Error;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        Regex::new(&pattern).map(Self)
+    }
+}
+
+impl From<Pattern> for String {
+    fn from(pattern: Pattern) -> Self {
+        pattern.0.as_str().to_string()
+    }
+}
+
+impl Assertions {
+    /// Whether the response body has to be read to evaluate the assertions
+    fn needs_body(&self) -> bool {
+        self.body_contains.is_some()
+            || self.body_matches.is_some()
+            || self.json.is_some()
+            || self.max_size.is_some()
+    }
+
+    /// Check the status code of a response
+    pub fn verify_status(&self, status: u16) -> Result<(), String> {
+        let accepted = match &self.status {
+            Some(ranges) => ranges.iter().any(|range| range.contains(status)),
+            None => (200..300).contains(&status),
+        };
+
+        if accepted {
+            return Ok(());
+        }
+
+        match &self.status {
+            Some(ranges) => Err(format!(
+                "unexpected status {}, expected {}",
+                status,
+                ranges
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )),
+            None => Err(format!("unexpected status {}", status)),
+        }
+    }
+
+    /// Check the headers of a response, `header` looking up a header by name
+    pub fn verify_headers<'a, F>(&self, header: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Option<&'a str>,
+    {
+        for (name, expected) in self.headers.iter().flatten() {
+            match (header(name), expected) {
+                (None, _) => return Err(format!("missing header {}", name)),
+                (Some(value), Some(expected)) if value != expected => {
+                    return Err(format!(
+                        "header {} is {:?}, expected {:?}",
+                        name, value, expected
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the body of a response
+    pub fn verify_body(&self, body: &[u8]) -> Result<(), String> {
+        if let Some(max_size) = self.max_size {
+            if body.len() as u64 > max_size {
+                return Err(format!(
+                    "body exceeds the maximum size of {} bytes",
+                    max_size
+                ));
+            }
+        }
+
+        let text = String::from_utf8_lossy(body);
+
+        if let Some(needle) = &self.body_contains {
+            if !text.contains(needle.as_str()) {
+                return Err(format!("body does not contain {:?}", needle));
+            }
+        }
+
+        if let Some(pattern) = &self.body_matches {
+            if !pattern.0.is_match(&text) {
+                return Err(format!("body does not match /{}/", pattern.0.as_str()));
+            }
+        }
+
+        if let Some(expected) = &self.json {
+            let json: Value = serde_json::from_slice(body)
+                .map_err(|err| format!("body is not valid JSON: {}", err))?;
+
+            for (path, expected) in expected {
+                match resolve(&json, path) {
+                    Some(value) if value == expected => {}
+                    Some(value) => {
+                        return Err(format!(
+                            "expected {} to be {}, got {}",
+                            path, expected, value
+                        ))
+                    }
+                    None => return Err(format!("{} is missing from the body", path)),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a response against every assertion, returning the first one which failed
+    ///
+    /// The body is only read if an assertion needs it or `keep_body` is set,
+    /// and is returned if it was.
+    pub async fn verify(
+        &self,
+        response: &mut Response,
+        keep_body: bool,
+    ) -> Result<Option<Vec<u8>>, String> {
+        self.verify_status(response.status().into())?;
+
+        self.verify_headers(|name| response.header(name).map(|values| values.last().as_str()))?;
+
+        if !self.needs_body() && !keep_body {
+            return Ok(None);
+        }
+
+        let body = read_body(response, self.max_size).await?;
+
+        self.verify_body(&body)?;
+
+        Ok(Some(body))
+    }
+}
+
+/// Keywords a page has to contain, and whether changes to it are reported
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    /// words the body has to contain
+    pub keywords: Option<Vec<String>>,
+    /// fail a check if the body differs from the previous check
+    /// default: false
+    pub detect_changes: Option<bool>,
+    /// regular expression matching volatile parts of the body, such as
+    /// timestamps or tokens, which are ignored when detecting changes
+    pub ignore: Option<Pattern>,
+    /// how badly a missing keyword or a change affects the endpoint, `"degraded"` or `"outage"`
+    /// default: outage
+    pub severity: Option<Severity>,
+}
+
+impl Content {
+    /// Check that the body contains every keyword
+    pub fn verify(&self, body: &[u8]) -> Result<(), Failure> {
+        let text = String::from_utf8_lossy(body);
+
+        match self
+            .keywords
+            .iter()
+            .flatten()
+            .find(|keyword| !text.contains(keyword.as_str()))
+        {
+            Some(keyword) => {
+                Err(self.failure(format!("keyword {:?} is missing from the body", keyword)))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Hash of the body with its volatile parts removed
+    pub fn hash(&self, body: &[u8]) -> u64 {
+        let text = String::from_utf8_lossy(body);
+
+        let stable = match &self.ignore {
+            Some(pattern) => pattern.0.replace_all(&text, ""),
+            None => text,
+        };
+
+        let mut hasher = DefaultHasher::new();
+        stable.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// A failure with the configured severity
+    pub fn failure(&self, reason: String) -> Failure {
+        Failure {
+            reason,
+            severity: self.severity.unwrap_or_default(),
+        }
+    }
+}
+
+/// Read a response body, stopping just past `limit` so oversized bodies aren't read in full
+async fn read_body(response: &mut Response, limit: Option<u64>) -> Result<Vec<u8>, String> {
+    let mut buf = vec![];
+
+    response
+        .take_body()
+        .into_reader()
+        .take(limit.map(|limit| limit + 1).unwrap_or(u64::MAX))
+        .read_to_end(&mut buf)
+        .await
+        .map_err(|err| format!("failed to read body: {}", err))?;
+
+    Ok(buf)
+}
+
+/// Look up a value in a JSON document by a path such as `$.checks[0].status`
+pub fn resolve<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.strip_prefix('$').unwrap_or(path);
+
+    let mut current = value;
+
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        let (key, indices) = match segment.find('[') {
+            Some(index) => segment.split_at(index),
+            None => (segment, ""),
+        };
+
+        if !key.is_empty() {
+            current = current.get(key)?;
+        }
+
+        for index in indices.split('[').skip(1) {
+            let index: usize = index.strip_suffix(']')?.parse().ok()?;
+
+            current = current.get(index)?;
+        }
+    }
+
+    Some(current)
+}
@@ -0,0 +1,255 @@
+use std::time::{Duration, Instant};
+
+use futures::{future, stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use smol::{future::FutureExt, Timer};
+use surf::Client;
+
+use crate::config::{
+    Config, Monitor, MonitorType, DEFAULT_CONCURRENT_CHECKS, DEFAULT_CONNECTION_TIMEOUT,
+};
+
+pub mod certificate;
+pub mod command;
+pub mod dns;
+pub mod flow;
+pub mod grpc;
+pub mod http;
+pub mod reachability;
+pub mod tcp;
+pub mod websocket;
+
+/// How badly a failed check affects the monitored endpoint
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    /// the endpoint works, but not as well as it should
+    Degraded,
+    /// the endpoint is down
+    #[default]
+    Outage,
+}
+
+impl Severity {
+    /// Status of the components of the endpoint on the status page
+    pub fn component_status(&self) -> &'static str {
+        match self {
+            Self::Degraded => "DEGRADEDPERFORMANCE",
+            Self::Outage => "MAJOROUTAGE",
+        }
+    }
+}
+
+/// Why a check failed, and how badly
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub reason: String,
+    pub severity: Severity,
+}
+
+impl Failure {
+    pub fn degraded(reason: String) -> Self {
+        Self {
+            reason,
+            severity: Severity::Degraded,
+        }
+    }
+}
+
+impl From<String> for Failure {
+    fn from(reason: String) -> Self {
+        Self {
+            reason,
+            severity: Severity::Outage,
+        }
+    }
+}
+
+/// The outcome of a single check of a monitored endpoint
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: String,
+    pub monitor: Monitor,
+    /// latency of the check, in milliseconds
+    pub latency: u128,
+    /// why the check failed, `None` if it succeeded
+    pub failure: Option<String>,
+    /// how badly the endpoint is affected, only meaningful if the check failed
+    pub severity: Severity,
+    /// additional information measured by the check
+    /// example: days until a certificate expires, or handshake times of a reachability or WebSocket check
+    pub detail: Option<String>,
+    /// hash of the response body, for HTTP monitors detecting content changes
+    pub content_hash: Option<u64>,
+    /// step of a flow monitor which failed
+    /// example: `step 2 (login)`
+    pub step: Option<String>,
+    /// number of times the endpoint was checked, more than 1 if failed attempts were retried
+    pub attempts: u32,
+}
+
+impl CheckResult {
+    pub fn is_success(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// Whether the latency of the check is above the warning threshold of its monitor
+    pub fn is_slow(&self) -> bool {
+        self.monitor
+            .latency_threshold
+            .is_some_and(|threshold| self.latency > u128::from(threshold.warning))
+    }
+}
+
+/// Delay before the first retry of a failed check, unless configured otherwise
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest delay between two retries of a failed check
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Check a monitor, retrying failed attempts up to `retries` times
+///
+/// The delay between attempts starts at `retryDelay` and doubles with every
+/// retry. The first attempt times out after the monitor's timeout, retries
+/// after its `retryTimeout`. The result is the one of the last attempt.
+pub async fn check_monitor(client: Client, name: String, monitor: Monitor) -> CheckResult {
+    let timeout = Duration::from_secs(monitor.timeout.unwrap_or(DEFAULT_CONNECTION_TIMEOUT));
+    let retry_timeout = monitor
+        .retry_timeout
+        .map(Duration::from_secs)
+        .unwrap_or(timeout);
+    let retries = monitor.retries.unwrap_or(0);
+
+    let mut delay = monitor
+        .retry_delay
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_RETRY_DELAY);
+
+    let mut result = attempt(&client, name, monitor, timeout).await;
+
+    while !result.is_success() && result.attempts <= retries {
+        tracing::warn!(
+            monitor = %result.name,
+            attempt = result.attempts,
+            retries,
+            delay_ms = delay.as_millis() as u64,
+            reason = result.failure.as_deref().unwrap_or_default(),
+            "check failed, retrying"
+        );
+
+        Timer::after(delay).await;
+
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+
+        let CheckResult {
+            name,
+            monitor,
+            attempts,
+            ..
+        } = result;
+
+        result = attempt(&client, name, monitor, retry_timeout).await;
+        result.attempts = attempts + 1;
+    }
+
+    if result.attempts > 1 {
+        tracing::info!(
+            monitor = %result.name,
+            attempts = result.attempts,
+            success = result.is_success(),
+            reason = result.failure.as_deref().unwrap_or_default(),
+            "check completed after retries"
+        );
+    }
+
+    result
+}
+
+/// Check a monitor once, timing out after `timeout`
+async fn attempt(
+    client: &Client,
+    name: String,
+    monitor: Monitor,
+    timeout: Duration,
+) -> CheckResult {
+    let start = Instant::now();
+    let deadline = start + timeout;
+
+    // set by the check once the endpoint has responded
+    let mut latency = None;
+    let mut detail = None;
+    let mut content_hash = None;
+    let mut step = None;
+
+    let outcome = async {
+        match monitor.type_ {
+            MonitorType::Uptime | MonitorType::Latency => {
+                http::check(client, &monitor, &mut latency, &mut content_hash).await
+            }
+            MonitorType::Flow => flow::check(client, &monitor, &mut latency, &mut step).await,
+            MonitorType::Tcp => Ok(tcp::check(&monitor, deadline, &mut latency).await?),
+            MonitorType::Dns => Ok(dns::check(&monitor, &mut latency).await?),
+            MonitorType::Reachability => {
+                Ok(reachability::check(&monitor, deadline, &mut latency, &mut detail).await?)
+            }
+            MonitorType::Grpc => grpc::check(&monitor, &mut latency).await,
+            MonitorType::WebSocket => {
+                Ok(websocket::check(&monitor, deadline, &mut latency, &mut detail).await?)
+            }
+            MonitorType::Command => command::check(&monitor, &mut latency, &mut detail).await,
+            MonitorType::Heartbeat => Err("heartbeats are only received while monitoring"
+                .to_string()
+                .into()),
+            MonitorType::Certificate => {
+                certificate::check(&monitor, &mut latency, &mut detail).await
+            }
+        }
+    }
+    .or(async {
+        Timer::at(deadline).await;
+
+        Err(format!("check timed out after {}s", timeout.as_secs()).into())
+    })
+    .await;
+
+    let latency = latency.unwrap_or_else(|| start.elapsed().as_millis());
+
+    let (failure, severity) = match outcome {
+        Ok(()) => (None, Severity::default()),
+        Err(failure) => (Some(failure.reason), failure.severity),
+    };
+
+    CheckResult {
+        name,
+        monitor,
+        latency,
+        failure,
+        severity,
+        detail,
+        content_hash,
+        step,
+        attempts: 1,
+    }
+}
+
+/// Check every monitor of `config` once, at most `maxConcurrentChecks` at a time
+///
+/// Heartbeat monitors are skipped, as heartbeats are only received while
+/// monitoring. Results are sorted by monitor name.
+pub async fn check_all(client: &Client, config: &Config) -> Vec<CheckResult> {
+    let mut results: Vec<CheckResult> = stream::iter(config.monitors.iter())
+        .filter(|(_, monitor)| future::ready(!matches!(monitor.type_, MonitorType::Heartbeat)))
+        .map(|(name, monitor)| check_monitor(client.clone(), name.clone(), monitor.clone()))
+        .buffer_unordered(
+            config
+                .max_concurrent_checks
+                .unwrap_or(DEFAULT_CONCURRENT_CHECKS)
+                .max(1),
+        )
+        .collect()
+        .await;
+
+    results.sort_by(|a, b| a.name.cmp(&b.name));
+
+    results
+}
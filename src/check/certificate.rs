@@ -0,0 +1,183 @@
+use std::{
+    io::BufReader,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use async_tls::TlsConnector;
+use chrono::Utc;
+use rustls::{
+    Certificate, ClientConfig, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError,
+    WebPKIVerifier,
+};
+use smol::net::TcpStream;
+
+use super::Failure;
+use crate::config::Monitor;
+
+/// Days before expiry a certificate is reported as degraded, unless configured otherwise
+pub const DEFAULT_EXPIRY_THRESHOLD: u64 = 14;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Host and port of a certificate monitor
+///
+/// `url` is either `host`, `host:port` or an `https://` URL, the port defaulting to 443.
+pub fn target(url: &str) -> Result<(String, u16), String> {
+    let invalid = || format!("invalid address {}, expected host, host:port or a URL", url);
+
+    if url.contains("://") {
+        let parsed = surf::Url::parse(url).map_err(|_| invalid())?;
+
+        return match (parsed.host_str(), parsed.port_or_known_default()) {
+            (Some(host), Some(port)) => Ok((host.to_string(), port)),
+            _ => Err(invalid()),
+        };
+    }
+
+    match url.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() => {
+            Ok((host.to_string(), port.parse().map_err(|_| invalid())?))
+        }
+        Some(_) => Err(invalid()),
+        None if !url.is_empty() => Ok((url.to_string(), 443)),
+        None => Err(invalid()),
+    }
+}
+
+/// TLS configuration trusting the usual roots, and the `caCertificate` of `monitor` if any
+pub async fn client_config(monitor: &Monitor) -> Result<ClientConfig, String> {
+    let mut config = ClientConfig::new();
+
+    config
+        .root_store
+        .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+
+    if let Some(path) = &monitor.ca_certificate {
+        let pem = smol::fs::read(path)
+            .await
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+
+        match config
+            .root_store
+            .add_pem_file(&mut BufReader::new(&pem[..]))
+        {
+            Ok((added, _)) if added > 0 => {}
+            _ => return Err(format!("no certificates found in {}", path.display())),
+        }
+    }
+
+    Ok(config)
+}
+
+/// Certificate verifier which lets every handshake complete, recording the
+/// presented chain and why it would have been rejected instead
+///
+/// This way an expired or mismatched certificate can still be inspected.
+struct Inspector {
+    verifier: WebPKIVerifier,
+    chain: Mutex<Vec<Certificate>>,
+    error: Mutex<Option<TLSError>>,
+}
+
+impl ServerCertVerifier for Inspector {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        dns_name: webpki::DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let verified =
+            self.verifier
+                .verify_server_cert(roots, presented_certs, dns_name, ocsp_response);
+
+        *self.chain.lock().unwrap() = presented_certs.to_vec();
+        *self.error.lock().unwrap() = verified.err();
+
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Human readable reason a certificate of `host` was rejected
+fn describe(error: TLSError, host: &str) -> String {
+    match error {
+        TLSError::WebPKIError(webpki::Error::CertExpired) => "certificate has expired".to_string(),
+        TLSError::WebPKIError(webpki::Error::CertNotValidYet) => {
+            "certificate is not valid yet".to_string()
+        }
+        TLSError::WebPKIError(webpki::Error::CertNotValidForName) => {
+            format!("certificate is not valid for {}", host)
+        }
+        TLSError::WebPKIError(webpki::Error::UnknownIssuer) => {
+            "certificate is not issued by a trusted authority".to_string()
+        }
+        error => error.to_string(),
+    }
+}
+
+/// Connect to a certificate monitor and inspect the certificate it presents
+///
+/// An invalid certificate fails the check as an outage, one expiring within
+/// `expiryThreshold` days as degraded. `detail` is set to the days until expiry.
+pub async fn check(
+    monitor: &Monitor,
+    latency: &mut Option<u128>,
+    detail: &mut Option<String>,
+) -> Result<(), Failure> {
+    let (host, port) = target(&monitor.url)?;
+
+    let mut config = client_config(monitor).await?;
+
+    let inspector = Arc::new(Inspector {
+        verifier: WebPKIVerifier::new(),
+        chain: Mutex::new(vec![]),
+        error: Mutex::new(None),
+    });
+
+    config
+        .dangerous()
+        .set_certificate_verifier(inspector.clone());
+
+    let start = Instant::now();
+
+    let stream = TcpStream::connect((host.as_str(), port))
+        .await
+        .map_err(|err| format!("failed to connect to {}:{}: {}", host, port, err))?;
+
+    TlsConnector::from(Arc::new(config))
+        .connect(&host, stream)
+        .await
+        .map_err(|err| format!("TLS handshake with {} failed: {}", host, err))?;
+
+    *latency = Some(start.elapsed().as_millis());
+
+    let chain = inspector.chain.lock().unwrap().clone();
+
+    let leaf = chain
+        .first()
+        .ok_or_else(|| format!("{} presented no certificate", host))?;
+
+    let (_, certificate) = x509_parser::parse_x509_certificate(&leaf.0)
+        .map_err(|err| format!("failed to parse certificate: {}", err))?;
+
+    let days = (certificate.validity().not_after.timestamp() - Utc::now().timestamp())
+        .div_euclid(SECONDS_PER_DAY);
+
+    *detail = Some(format!("certificate expires in {} days", days));
+
+    if let Some(error) = inspector.error.lock().unwrap().take() {
+        return Err(describe(error, &host).into());
+    }
+
+    let threshold = monitor.expiry_threshold.unwrap_or(DEFAULT_EXPIRY_THRESHOLD);
+
+    if days < threshold as i64 {
+        return Err(Failure::degraded(format!(
+            "certificate expires in {} days, within the threshold of {} days",
+            days, threshold
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::time::Instant;
+
+use smol::process::{Command, Stdio};
+
+use super::{Failure, Severity};
+use crate::config::Monitor;
+
+/// Longest output attached to a check result, in characters
+const MAX_OUTPUT_LENGTH: usize = 200;
+
+/// First line a command printed, preferring stdout over stderr
+fn summary(stdout: &str, stderr: &str) -> Option<String> {
+    let line = stdout
+        .lines()
+        .chain(stderr.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())?;
+
+    Some(line.chars().take(MAX_OUTPUT_LENGTH).collect())
+}
+
+/// Run the command of a command monitor, following the exit codes of Nagios plugins
+///
+/// Exit code 0 means the check passed, 1 that the endpoint is degraded and
+/// anything else that it is down. `detail` is set to the first line of output.
+/// The command is killed if the check times out.
+pub async fn check(
+    monitor: &Monitor,
+    latency: &mut Option<u128>,
+    detail: &mut Option<String>,
+) -> Result<(), Failure> {
+    let (program, args) = match monitor.command.as_deref() {
+        Some([program, args @ ..]) => (program, args),
+        _ => return Err("no command configured".to_string().into()),
+    };
+
+    let start = Instant::now();
+
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|err| format!("failed to run {}: {}", program, err))?;
+
+    *latency = Some(start.elapsed().as_millis());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    *detail = summary(&stdout, &stderr);
+
+    let severity = match output.status.code() {
+        Some(0) => None,
+        Some(1) => Some(Severity::Degraded),
+        _ => Some(Severity::Outage),
+    };
+
+    if let Some(severity) = severity {
+        let status = match output.status.code() {
+            Some(code) => format!("{} exited with status {}", program, code),
+            None => format!("{} was terminated by a signal", program),
+        };
+
+        return Err(Failure {
+            reason: match &detail {
+                Some(output) => format!("{}: {}", status, output),
+                None => status,
+            },
+            severity,
+        });
+    }
+
+    if let Some(pattern) = &monitor.output_matches {
+        if !pattern.0.is_match(&stdout) {
+            return Err(format!("output does not match /{}/", pattern.0.as_str()).into());
+        }
+    }
+
+    Ok(())
+}
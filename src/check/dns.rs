@@ -0,0 +1,216 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+use simple_dns::{rdata::RData, Name, Packet, PacketFlag, Question, CLASS, RCODE, TYPE};
+use smol::net::UdpSocket;
+
+use crate::config::Monitor;
+
+/// Resolver used when none is configured and none can be read from `/etc/resolv.conf`
+pub const FALLBACK_RESOLVER: &str = "1.1.1.1:53";
+
+/// Type of the records a DNS monitor resolves
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RecordType {
+    #[default]
+    A,
+    Aaaa,
+    Cname,
+    Mx,
+    Ns,
+    Txt,
+}
+
+impl RecordType {
+    fn to_type(self) -> TYPE {
+        match self {
+            Self::A => TYPE::A,
+            Self::Aaaa => TYPE::AAAA,
+            Self::Cname => TYPE::CNAME,
+            Self::Mx => TYPE::MX,
+            Self::Ns => TYPE::NS,
+            Self::Txt => TYPE::TXT,
+        }
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::A => "A",
+            Self::Aaaa => "AAAA",
+            Self::Cname => "CNAME",
+            Self::Mx => "MX",
+            Self::Ns => "NS",
+            Self::Txt => "TXT",
+        })
+    }
+}
+
+/// Conventional name of a response code, as shown by tools such as `dig`
+fn rcode_name(rcode: RCODE) -> String {
+    match rcode {
+        RCODE::FormatError => "FORMERR".to_string(),
+        RCODE::ServerFailure => "SERVFAIL".to_string(),
+        RCODE::NameError => "NXDOMAIN".to_string(),
+        RCODE::NotImplemented => "NOTIMP".to_string(),
+        RCODE::Refused => "REFUSED".to_string(),
+        rcode => format!("{:?}", rcode),
+    }
+}
+
+/// Parse a resolver address, either `ip` or `ip:port`
+pub fn resolver_address(resolver: &str) -> Result<SocketAddr, String> {
+    resolver
+        .parse::<SocketAddr>()
+        .or_else(|_| resolver.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("invalid resolver {}, expected ip or ip:port", resolver))
+}
+
+/// First nameserver configured in `/etc/resolv.conf`, if any
+fn system_resolver() -> Option<SocketAddr> {
+    let resolv_conf = std::fs::read_to_string("/etc/resolv.conf").ok()?;
+
+    resolv_conf.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+
+        match (fields.next(), fields.next()) {
+            (Some("nameserver"), Some(ip)) => ip.parse().ok().map(|ip| SocketAddr::new(ip, 53)),
+            _ => None,
+        }
+    })
+}
+
+/// Normalise a name so answers can be compared regardless of case or a trailing dot
+fn normalize(answer: &str) -> String {
+    answer.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Text representation of a record, `None` for records of other types
+fn format_answer(rdata: &RData) -> Option<String> {
+    match rdata {
+        RData::A(a) => Some(Ipv4Addr::from(a.address).to_string()),
+        RData::AAAA(aaaa) => Some(Ipv6Addr::from(aaaa.address).to_string()),
+        RData::CNAME(cname) => Some(cname.0.to_string()),
+        RData::NS(ns) => Some(ns.0.to_string()),
+        RData::MX(mx) => Some(format!("{} {}", mx.preference, mx.exchange)),
+        RData::TXT(txt) => String::try_from(txt.clone()).ok(),
+        _ => None,
+    }
+}
+
+/// Resolve the name of a DNS monitor, checking its answers and resolution time
+pub async fn check(monitor: &Monitor, latency: &mut Option<u128>) -> Result<(), String> {
+    let record_type = monitor.record_type.unwrap_or_default();
+
+    let resolver = match &monitor.resolver {
+        Some(resolver) => resolver_address(resolver)?,
+        None => system_resolver().unwrap_or_else(|| FALLBACK_RESOLVER.parse().unwrap()),
+    };
+
+    let name =
+        Name::new(&monitor.url).map_err(|err| format!("invalid name {}: {}", monitor.url, err))?;
+
+    let id = fastrand::u16(..);
+
+    let mut query = Packet::new_query(id);
+    query.set_flags(PacketFlag::RECURSION_DESIRED);
+    query.questions.push(Question::new(
+        name,
+        record_type.to_type().into(),
+        CLASS::IN.into(),
+        false,
+    ));
+
+    let query = query
+        .build_bytes_vec()
+        .map_err(|err| format!("failed to build query: {}", err))?;
+
+    let bind: SocketAddr = match resolver {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+
+    let socket = UdpSocket::bind(bind)
+        .await
+        .map_err(|err| format!("failed to open socket: {}", err))?;
+
+    let start = Instant::now();
+
+    socket
+        .send_to(&query, resolver)
+        .await
+        .map_err(|err| format!("failed to query {}: {}", resolver, err))?;
+
+    let mut buf = [0; 4096];
+
+    // ignore stray datagrams which aren't the answer to this query
+    let reply = loop {
+        let (len, from) = socket
+            .recv_from(&mut buf)
+            .await
+            .map_err(|err| format!("failed to read answer from {}: {}", resolver, err))?;
+
+        if from != resolver {
+            continue;
+        }
+
+        match Packet::parse(&buf[..len]) {
+            Ok(reply) if reply.id() == id => break reply,
+            _ => continue,
+        }
+    };
+
+    let elapsed = start.elapsed().as_millis();
+
+    *latency = Some(elapsed);
+
+    if reply.rcode() != RCODE::NoError {
+        return Err(format!(
+            "{} answered {} for {}",
+            resolver,
+            rcode_name(reply.rcode()),
+            monitor.url
+        ));
+    }
+
+    let answers: Vec<String> = reply
+        .answers
+        .iter()
+        .filter(|record| record.rdata.type_code() == record_type.to_type())
+        .filter_map(|record| format_answer(&record.rdata))
+        .collect();
+
+    if answers.is_empty() {
+        return Err(format!("no {} records for {}", record_type, monitor.url));
+    }
+
+    let normalized: Vec<String> = answers.iter().map(|answer| normalize(answer)).collect();
+
+    for expected in monitor.answers.iter().flatten() {
+        if !normalized.contains(&normalize(expected)) {
+            return Err(format!(
+                "expected {} to resolve to {}, got {}",
+                monitor.url,
+                expected,
+                answers.join(", ")
+            ));
+        }
+    }
+
+    if let Some(max) = monitor.max_resolution_time {
+        if elapsed > max as u128 {
+            return Err(format!(
+                "resolution took {} ms, longer than the maximum of {} ms",
+                elapsed, max
+            ));
+        }
+    }
+
+    Ok(())
+}
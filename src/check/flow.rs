@@ -0,0 +1,301 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use surf::{http::Method, Client, Response};
+
+use super::Failure;
+use crate::{
+    assertion::{resolve, Assertions, Pattern},
+    config::Monitor,
+    request::{self, Auth, RequestBody, Secret},
+};
+
+/// A request made by a flow monitor
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Step {
+    /// name of the step in failure reasons and incidents
+    /// default: the number of the step
+    pub name: Option<String>,
+    /// URL the request is sent to, which may use variables as `{{name}}`
+    pub url: String,
+    /// HTTP method of the request
+    /// default: GET
+    pub method: Option<Method>,
+    /// headers sent with the request, values may use variables
+    pub headers: Option<BTreeMap<String, Secret>>,
+    /// body sent with the request, text and JSON strings may use variables
+    pub body: Option<RequestBody>,
+    /// credentials sent with the request
+    pub auth: Option<Auth>,
+    /// conditions the response has to meet for the flow to continue
+    /// default: any 2xx status
+    pub assertions: Option<Assertions>,
+    /// values extracted from the response into variables used by later steps, by variable name
+    /// example: `{ "token": { "json": "$.token" }, "session": { "header": "X-Session" } }`
+    pub extract: Option<BTreeMap<String, Extractor>>,
+}
+
+/// Where the value of a variable is extracted from
+///
+/// example: `{ "json": "$.user.id" }`, `{ "header": "Location" }` or `{ "regex": "csrf=(\\w+)" }`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Extractor {
+    /// value at a path of a JSON body
+    Json { json: String },
+    /// value of a response header
+    Header { header: String },
+    /// first group captured by a regular expression in the body, or the whole match without groups
+    Regex { regex: Pattern },
+}
+
+impl Extractor {
+    fn needs_body(&self) -> bool {
+        !matches!(self, Self::Header { .. })
+    }
+
+    fn extract(&self, response: &Response, body: &[u8]) -> Result<String, String> {
+        match self {
+            Self::Json { json } => {
+                let value: Value = serde_json::from_slice(body)
+                    .map_err(|err| format!("body is not valid JSON: {}", err))?;
+
+                match resolve(&value, json) {
+                    Some(Value::String(value)) => Ok(value.clone()),
+                    Some(value) => Ok(value.to_string()),
+                    None => Err(format!("{} is missing from the body", json)),
+                }
+            }
+            Self::Header { header } => response
+                .header(header.as_str())
+                .map(|values| values.last().as_str().to_string())
+                .ok_or_else(|| format!("missing header {}", header)),
+            Self::Regex { regex } => {
+                let text = String::from_utf8_lossy(body);
+
+                let captures = regex
+                    .0
+                    .captures(&text)
+                    .ok_or_else(|| format!("body does not match /{}/", regex.0.as_str()))?;
+
+                let value = captures.get(1).or_else(|| captures.get(0)).unwrap();
+
+                Ok(value.as_str().to_string())
+            }
+        }
+    }
+}
+
+impl Step {
+    /// Label of the step numbered `number`, as used in failure reasons
+    /// example: `step 2 (login)`
+    pub fn label(&self, number: usize) -> String {
+        match &self.name {
+            Some(name) => format!("step {} ({})", number, name),
+            None => format!("step {}", number),
+        }
+    }
+
+    /// Names of the variables used by the URL, headers and text body of the step
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = variables(&self.url);
+
+        for value in self.headers.iter().flatten().map(|(_, value)| value) {
+            if let Secret::Value(value) = value {
+                names.extend(variables(value));
+            }
+        }
+
+        if let Some(RequestBody::Text(text)) = &self.body {
+            names.extend(variables(text));
+        }
+
+        names
+    }
+}
+
+/// Names of the variables used in `text`, as `{{name}}`
+fn variables(text: &str) -> Vec<&str> {
+    let mut names = vec![];
+    let mut rest = text;
+
+    while let Some((_, after)) = rest.split_once("{{") {
+        match after.split_once("}}") {
+            Some((name, after)) => {
+                names.push(name.trim());
+                rest = after;
+            }
+            None => break,
+        }
+    }
+
+    names
+}
+
+/// Replace the variables used in `text` with their value
+fn substitute(text: &str, values: &HashMap<String, String>) -> Result<String, String> {
+    let mut substituted = String::new();
+    let mut rest = text;
+
+    while let Some((before, after)) = rest.split_once("{{") {
+        let (name, after) = match after.split_once("}}") {
+            Some(split) => split,
+            None => break,
+        };
+
+        let value = values
+            .get(name.trim())
+            .ok_or_else(|| format!("variable {} is not defined", name.trim()))?;
+
+        substituted.push_str(before);
+        substituted.push_str(value);
+        rest = after;
+    }
+
+    substituted.push_str(rest);
+
+    Ok(substituted)
+}
+
+/// Replace the variables used in the strings of a JSON value
+fn substitute_json(value: &Value, values: &HashMap<String, String>) -> Result<Value, String> {
+    Ok(match value {
+        Value::String(text) => Value::String(substitute(text, values)?),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| substitute_json(item, values))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), substitute_json(value, values)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        value => value.clone(),
+    })
+}
+
+/// The monitor a step is sent as, with the variables it uses substituted
+///
+/// Headers and credentials of the flow monitor are sent with every step,
+/// unless the step overrides them.
+fn step_monitor(
+    monitor: &Monitor,
+    step: &Step,
+    values: &HashMap<String, String>,
+) -> Result<Monitor, String> {
+    let mut headers = monitor.headers.clone().unwrap_or_default();
+
+    for (name, value) in step.headers.iter().flatten() {
+        let value = match value {
+            Secret::Value(value) => Secret::Value(substitute(value, values)?),
+            secret => secret.clone(),
+        };
+
+        headers.insert(name.clone(), value);
+    }
+
+    let body = match &step.body {
+        Some(RequestBody::Text(text)) => Some(RequestBody::Text(substitute(text, values)?)),
+        Some(RequestBody::Json { json }) => Some(RequestBody::Json {
+            json: substitute_json(json, values)?,
+        }),
+        body => body.clone(),
+    };
+
+    Ok(Monitor {
+        url: substitute(&step.url, values)?,
+        method: step.method,
+        headers: Some(headers),
+        body,
+        auth: step.auth.clone().or_else(|| monitor.auth.clone()),
+        assertions: step.assertions.clone(),
+        ..monitor.clone()
+    })
+}
+
+/// Run a single step, returning the variables it extracted
+async fn run(
+    client: &Client,
+    monitor: &Monitor,
+    step: &Step,
+    values: &HashMap<String, String>,
+    latency: &mut u128,
+) -> Result<Vec<(String, String)>, String> {
+    let monitor = step_monitor(monitor, step, values)?;
+
+    let request = request::build(client, &monitor).await?;
+
+    let start = Instant::now();
+
+    let mut response = request.send().await.map_err(|err| err.to_string())?;
+
+    *latency += start.elapsed().as_millis();
+
+    let extract = step.extract.as_ref();
+    let keep_body = extract.into_iter().flatten().any(|(_, e)| e.needs_body());
+
+    let default = Assertions::default();
+    let body = monitor
+        .assertions
+        .as_ref()
+        .unwrap_or(&default)
+        .verify(&mut response, keep_body)
+        .await?
+        .unwrap_or_default();
+
+    extract
+        .into_iter()
+        .flatten()
+        .map(|(name, extractor)| {
+            extractor
+                .extract(&response, &body)
+                .map(|value| (name.clone(), value))
+                .map_err(|err| format!("failed to extract {}: {}", name, err))
+        })
+        .collect()
+}
+
+/// Run the steps of a flow monitor in order, stopping at the first which fails
+///
+/// Values extracted by a step are available to the following ones as
+/// variables. `latency` is set to the time taken by every request, and `step`
+/// to the label of the step which failed.
+pub async fn check(
+    client: &Client,
+    monitor: &Monitor,
+    latency: &mut Option<u128>,
+    step: &mut Option<String>,
+) -> Result<(), Failure> {
+    let steps = match monitor.steps.as_deref() {
+        Some(steps) if !steps.is_empty() => steps,
+        _ => return Err("no steps configured".to_string().into()),
+    };
+
+    let mut values = HashMap::new();
+    let mut total = 0;
+
+    for (index, current) in steps.iter().enumerate() {
+        let label = current.label(index + 1);
+
+        match run(client, monitor, current, &values, &mut total).await {
+            Ok(extracted) => values.extend(extracted),
+            Err(err) => {
+                *step = Some(label.clone());
+
+                return Err(format!("{} failed: {}", label, err).into());
+            }
+        }
+    }
+
+    *latency = Some(total);
+
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use async_compat::Compat;
+use async_tls::TlsConnector;
+use bytes::Bytes;
+use http::{HeaderMap, Request};
+use smol::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use super::{certificate, Failure};
+use crate::config::Monitor;
+
+/// Path of the RPC defined by the gRPC health checking protocol
+const HEALTH_CHECK_PATH: &str = "/grpc.health.v1.Health/Check";
+
+/// `ServingStatus` of a `HealthCheckResponse`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ServingStatus {
+    Unknown,
+    Serving,
+    NotServing,
+    ServiceUnknown,
+}
+
+impl ServingStatus {
+    fn from_code(status: u64) -> Option<Self> {
+        match status {
+            0 => Some(Self::Unknown),
+            1 => Some(Self::Serving),
+            2 => Some(Self::NotServing),
+            3 => Some(Self::ServiceUnknown),
+            _ => None,
+        }
+    }
+}
+
+/// Name of a gRPC status code
+fn status_name(code: &str) -> &str {
+    match code {
+        "1" => "CANCELLED",
+        "2" => "UNKNOWN",
+        "3" => "INVALID_ARGUMENT",
+        "4" => "DEADLINE_EXCEEDED",
+        "5" => "NOT_FOUND",
+        "7" => "PERMISSION_DENIED",
+        "8" => "RESOURCE_EXHAUSTED",
+        "12" => "UNIMPLEMENTED",
+        "13" => "INTERNAL",
+        "14" => "UNAVAILABLE",
+        "16" => "UNAUTHENTICATED",
+        code => code,
+    }
+}
+
+/// Whether a gRPC monitor uses TLS, `url` being an `http://` or `https://` URL
+pub fn uses_tls(url: &str) -> Result<bool, String> {
+    match surf::Url::parse(url).map(|url| url.scheme().to_string()) {
+        Ok(scheme) if scheme == "http" => Ok(false),
+        Ok(scheme) if scheme == "https" => Ok(true),
+        _ => Err(format!(
+            "invalid URL {}, expected http://host:port or https://host:port",
+            url
+        )),
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    buf.push(value as u8);
+}
+
+fn read_varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = buf.split_first()?;
+        *buf = rest;
+
+        value |= u64::from(byte & 0x7f) << shift;
+
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+/// A `HealthCheckRequest` for `service`, framed as a gRPC message
+fn encode_request(service: &str) -> Bytes {
+    let mut message = vec![];
+
+    if !service.is_empty() {
+        // field 1, length delimited
+        message.push(0x0a);
+        write_varint(&mut message, service.len() as u64);
+        message.extend_from_slice(service.as_bytes());
+    }
+
+    let mut frame = vec![0];
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&message);
+
+    frame.into()
+}
+
+/// The status of a `HealthCheckResponse` framed as a gRPC message
+fn decode_response(frame: &[u8]) -> Result<ServingStatus, String> {
+    let invalid = || "invalid health check response".to_string();
+
+    let (header, message) = match frame {
+        [0, header @ ..] if header.len() >= 4 => header.split_at(4),
+        [1, ..] => return Err("compressed health check responses are not supported".to_string()),
+        _ => return Err(invalid()),
+    };
+
+    let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let mut message = message.get(..len).ok_or_else(invalid)?;
+
+    // fields missing from a message have their default value, UNKNOWN for the status
+    let mut status = 0;
+
+    while !message.is_empty() {
+        let key = read_varint(&mut message).ok_or_else(invalid)?;
+
+        match key & 0x7 {
+            0 => {
+                let value = read_varint(&mut message).ok_or_else(invalid)?;
+
+                if key >> 3 == 1 {
+                    status = value;
+                }
+            }
+            2 => {
+                let len = read_varint(&mut message).ok_or_else(invalid)? as usize;
+                message = message.get(len..).ok_or_else(invalid)?;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    ServingStatus::from_code(status).ok_or_else(invalid)
+}
+
+/// Error of a call which did not complete with an OK status, if any
+fn call_error(headers: &HeaderMap) -> Option<String> {
+    let status = headers.get("grpc-status")?.to_str().unwrap_or_default();
+
+    if status == "0" {
+        return None;
+    }
+
+    let message = headers
+        .get("grpc-message")
+        .and_then(|message| message.to_str().ok())
+        .unwrap_or_default();
+
+    Some(format!(
+        "health check failed with status {}{}{}",
+        status_name(status),
+        if message.is_empty() { "" } else { ": " },
+        message
+    ))
+}
+
+/// TCP stream waking its task whenever data is read from it
+///
+/// async-tls also reads incoming records while writing, without waking the
+/// task waiting to read them. An HTTP/2 connection would stall whenever a
+/// response arrives during a write.
+struct WakeOnRead(TcpStream);
+
+impl futures::AsyncRead for WakeOnRead {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.0).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(read)) = poll {
+            if read > 0 {
+                cx.waker().wake_by_ref();
+            }
+        }
+
+        poll
+    }
+}
+
+impl futures::AsyncWrite for WakeOnRead {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+}
+
+/// Call `Health/Check` over an HTTP/2 connection
+async fn call<T>(io: T, authority: &str, tls: bool, service: &str) -> Result<ServingStatus, String>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (client, connection) = h2::client::handshake(io)
+        .await
+        .map_err(|err| format!("HTTP/2 handshake failed: {}", err))?;
+
+    // drives the connection until the call is done and the task is dropped
+    let _connection = smol::spawn(async move {
+        let _ = connection.await;
+    });
+
+    let request = Request::post(format!(
+        "{}://{}{}",
+        if tls { "https" } else { "http" },
+        authority,
+        HEALTH_CHECK_PATH
+    ))
+    .header("content-type", "application/grpc")
+    .header("te", "trailers")
+    .body(())
+    .map_err(|err| err.to_string())?;
+
+    let call_failed = |err: h2::Error| format!("health check failed: {}", err);
+
+    let mut client = client.ready().await.map_err(call_failed)?;
+
+    let (response, mut stream) = client.send_request(request, false).map_err(call_failed)?;
+
+    stream
+        .send_data(encode_request(service), true)
+        .map_err(call_failed)?;
+
+    let (parts, mut body) = response.await.map_err(call_failed)?.into_parts();
+
+    if parts.status != http::StatusCode::OK {
+        return Err(format!("unexpected HTTP status {}", parts.status.as_u16()));
+    }
+
+    // errors may be returned without a body, with the status in the headers
+    if let Some(err) = call_error(&parts.headers) {
+        return Err(err);
+    }
+
+    let mut frame = vec![];
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(call_failed)?;
+
+        let _ = body.flow_control().release_capacity(chunk.len());
+
+        frame.extend_from_slice(&chunk);
+    }
+
+    if let Some(err) = body
+        .trailers()
+        .await
+        .map_err(call_failed)?
+        .as_ref()
+        .and_then(call_error)
+    {
+        return Err(err);
+    }
+
+    decode_response(&frame)
+}
+
+/// Call the standard health checking RPC of a gRPC monitor
+///
+/// A SERVING status passes the check, UNKNOWN is reported as degraded and
+/// anything else, including a failed call, as down.
+pub async fn check(monitor: &Monitor, latency: &mut Option<u128>) -> Result<(), Failure> {
+    let tls = uses_tls(&monitor.url)?;
+
+    let url = surf::Url::parse(&monitor.url).map_err(|err| err.to_string())?;
+    let host = url.host_str().unwrap_or_default().to_string();
+    let port = url.port_or_known_default().unwrap_or(80);
+    let authority = format!("{}:{}", host, port);
+
+    let service = monitor.service.as_deref().unwrap_or_default();
+
+    let start = Instant::now();
+
+    let stream = TcpStream::connect(authority.as_str())
+        .await
+        .map_err(|err| format!("failed to connect to {}: {}", authority, err))?;
+
+    let status = if tls {
+        let mut config = certificate::client_config(monitor).await?;
+        config.set_protocols(&[b"h2".to_vec()]);
+
+        let stream = TlsConnector::from(Arc::new(config))
+            .connect(&host, WakeOnRead(stream))
+            .await
+            .map_err(|err| format!("TLS handshake with {} failed: {}", host, err))?;
+
+        call(Compat::new(stream), &authority, tls, service).await?
+    } else {
+        call(Compat::new(stream), &authority, tls, service).await?
+    };
+
+    *latency = Some(start.elapsed().as_millis());
+
+    let service = if service.is_empty() {
+        "server"
+    } else {
+        service
+    };
+
+    match status {
+        ServingStatus::Serving => Ok(()),
+        ServingStatus::Unknown => Err(Failure::degraded(format!("{} health is UNKNOWN", service))),
+        ServingStatus::NotServing => Err(format!("{} is NOT_SERVING", service).into()),
+        ServingStatus::ServiceUnknown => Err(format!("{} is SERVICE_UNKNOWN", service).into()),
+    }
+}
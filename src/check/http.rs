@@ -0,0 +1,43 @@
+use std::time::Instant;
+
+use surf::Client;
+
+use super::Failure;
+use crate::{assertion::Assertions, config::Monitor, request};
+
+/// Send the request of an HTTP monitor and verify the response against its assertions
+///
+/// If the monitor detects content changes, `content_hash` is set to the hash
+/// of the body, to be compared with the one of the previous check.
+pub async fn check(
+    client: &Client,
+    monitor: &Monitor,
+    latency: &mut Option<u128>,
+    content_hash: &mut Option<u64>,
+) -> Result<(), Failure> {
+    let request = request::build(client, monitor).await?;
+
+    let start = Instant::now();
+
+    let mut response = request.send().await.map_err(|err| err.to_string())?;
+
+    *latency = Some(start.elapsed().as_millis());
+
+    let default = Assertions::default();
+    let assertions = monitor.assertions.as_ref().unwrap_or(&default);
+
+    // reading the body for assertions counts towards the timeout, but not the latency
+    let body = assertions
+        .verify(&mut response, monitor.content.is_some())
+        .await?;
+
+    if let (Some(content), Some(body)) = (&monitor.content, body) {
+        content.verify(&body)?;
+
+        if content.detect_changes.unwrap_or(false) {
+            *content_hash = Some(content.hash(&body));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use smol::{future::FutureExt, net::TcpStream, Timer};
+
+use super::tcp;
+use crate::config::Monitor;
+
+/// Number of handshakes timed by a reachability monitor, unless configured otherwise
+pub const DEFAULT_ATTEMPTS: u32 = 5;
+
+/// Round-trip times of the handshakes of a reachability check
+struct Timings {
+    attempts: u32,
+    /// handshake durations of the attempts which succeeded
+    successes: Vec<Duration>,
+}
+
+impl Timings {
+    fn lost(&self) -> u32 {
+        self.attempts - self.successes.len() as u32
+    }
+
+    /// Percentage of attempts which failed
+    fn loss(&self) -> f64 {
+        if self.attempts == 0 {
+            return 0.0;
+        }
+
+        f64::from(self.lost()) * 100.0 / f64::from(self.attempts)
+    }
+
+    fn min(&self) -> Option<Duration> {
+        self.successes.iter().min().copied()
+    }
+
+    fn max(&self) -> Option<Duration> {
+        self.successes.iter().max().copied()
+    }
+
+    fn avg(&self) -> Option<Duration> {
+        if self.successes.is_empty() {
+            return None;
+        }
+
+        Some(self.successes.iter().sum::<Duration>() / self.successes.len() as u32)
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Time TCP handshakes with a reachability monitor, one attempt after the other
+///
+/// Every attempt gets an equal share of the time left until `deadline`. The
+/// check fails if no attempt succeeds, or if more than `maxLoss` percent of
+/// them fail. `latency` is set to the average handshake time and `detail` to
+/// the min/avg/max and loss.
+pub async fn check(
+    monitor: &Monitor,
+    deadline: Instant,
+    latency: &mut Option<u128>,
+    detail: &mut Option<String>,
+) -> Result<(), String> {
+    let address = tcp::address(&monitor.url)?;
+
+    let attempts = monitor.attempts.unwrap_or(DEFAULT_ATTEMPTS).max(1);
+
+    let mut timings = Timings {
+        attempts,
+        successes: vec![],
+    };
+    let mut last_error = None;
+
+    for attempt in 0..attempts {
+        let budget = deadline.saturating_duration_since(Instant::now()) / (attempts - attempt);
+
+        let start = Instant::now();
+
+        let connected = TcpStream::connect(address)
+            .or(async {
+                Timer::after(budget).await;
+
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "handshake timed out",
+                ))
+            })
+            .await;
+
+        match connected {
+            Ok(_) => timings.successes.push(start.elapsed()),
+            Err(err) => last_error = Some(err),
+        }
+    }
+
+    if let (Some(min), Some(avg), Some(max)) = (timings.min(), timings.avg(), timings.max()) {
+        *latency = Some(avg.as_millis());
+        *detail = Some(format!(
+            "min/avg/max {:.1}/{:.1}/{:.1} ms, {:.0}% loss",
+            millis(min),
+            millis(avg),
+            millis(max),
+            timings.loss()
+        ));
+    }
+
+    match (last_error, monitor.max_loss) {
+        (Some(err), _) if timings.successes.is_empty() => Err(format!(
+            "{} is unreachable, all {} attempts failed: {}",
+            address, attempts, err
+        )),
+        (Some(err), Some(max_loss)) if timings.loss() > max_loss => Err(format!(
+            "{:.0}% of attempts to reach {} failed, more than the maximum of {}%: {}",
+            timings.loss(),
+            address,
+            max_loss,
+            err
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,77 @@
+use std::time::Instant;
+
+use smol::{future::FutureExt, Timer};
+
+use futures::{AsyncReadExt, AsyncWriteExt};
+use smol::net::TcpStream;
+
+use crate::config::Monitor;
+
+/// Most bytes read from a connection while waiting for the expected banner
+const MAX_BANNER_SIZE: usize = 64 * 1024;
+
+/// Address of a TCP monitor, `url` being either `host:port` or `tcp://host:port`
+pub fn address(url: &str) -> Result<&str, String> {
+    let address = url.strip_prefix("tcp://").unwrap_or(url);
+
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(address),
+        _ => Err(format!("invalid address {}, expected host:port", url)),
+    }
+}
+
+/// Open a connection to a TCP monitor, sending its probe and waiting for its banner
+///
+/// If the banner hasn't been received by `deadline` the check fails with
+/// whatever the endpoint did respond with.
+pub async fn check(
+    monitor: &Monitor,
+    deadline: Instant,
+    latency: &mut Option<u128>,
+) -> Result<(), String> {
+    let address = address(&monitor.url)?;
+
+    let start = Instant::now();
+
+    let mut stream = TcpStream::connect(address)
+        .await
+        .map_err(|err| format!("failed to connect to {}: {}", address, err))?;
+
+    *latency = Some(start.elapsed().as_millis());
+
+    if let Some(probe) = &monitor.probe {
+        stream
+            .write_all(probe.as_bytes())
+            .await
+            .map_err(|err| format!("failed to send probe: {}", err))?;
+    }
+
+    if let Some(banner) = &monitor.banner {
+        let mut received = vec![];
+        let mut buf = [0; 1024];
+
+        while !String::from_utf8_lossy(&received).contains(banner.as_str()) {
+            let read = stream
+                .read(&mut buf)
+                .or(async {
+                    Timer::at(deadline).await;
+
+                    Ok(0)
+                })
+                .await
+                .map_err(|err| format!("failed to read banner: {}", err))?;
+
+            if read == 0 || received.len() >= MAX_BANNER_SIZE {
+                return Err(format!(
+                    "expected banner {:?}, got {:?}",
+                    banner,
+                    String::from_utf8_lossy(&received)
+                ));
+            }
+
+            received.extend_from_slice(&buf[..read]);
+        }
+    }
+
+    Ok(())
+}
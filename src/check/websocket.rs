@@ -0,0 +1,162 @@
+use std::{sync::Arc, time::Instant};
+
+use async_tls::TlsConnector;
+use async_tungstenite::tungstenite::{
+    client::IntoClientRequest,
+    handshake::client::Request,
+    http::{HeaderName, HeaderValue},
+    Message,
+};
+use futures::{AsyncRead, AsyncWrite, SinkExt, StreamExt};
+use smol::{future::FutureExt, net::TcpStream, Timer};
+
+use super::certificate;
+use crate::config::Monitor;
+
+/// Longest reply quoted in the reason a check failed, in characters
+const MAX_REPLY_LENGTH: usize = 200;
+
+/// Whether a WebSocket monitor uses TLS, `url` being a `ws://` or `wss://` URL
+pub fn uses_tls(url: &str) -> Result<bool, String> {
+    match surf::Url::parse(url).map(|url| url.scheme().to_string()) {
+        Ok(scheme) if scheme == "ws" => Ok(false),
+        Ok(scheme) if scheme == "wss" => Ok(true),
+        _ => Err(format!(
+            "invalid URL {}, expected ws://host/path or wss://host/path",
+            url
+        )),
+    }
+}
+
+fn millis(start: Instant, end: Instant) -> u128 {
+    end.duration_since(start).as_millis()
+}
+
+/// Upgrade the connection, then send the probe and wait for the banner if configured
+async fn exchange<S>(
+    monitor: &Monitor,
+    request: Request,
+    stream: S,
+    start: Instant,
+    deadline: Instant,
+    latency: &mut Option<u128>,
+    detail: &mut Option<String>,
+) -> Result<(), String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut socket, _) = async_tungstenite::client_async(request, stream)
+        .await
+        .map_err(|err| format!("WebSocket handshake failed: {}", err))?;
+
+    let handshake = Instant::now();
+
+    *latency = Some(millis(start, handshake));
+
+    if let Some(probe) = &monitor.probe {
+        socket
+            .send(Message::Text(probe.clone()))
+            .await
+            .map_err(|err| format!("failed to send probe: {}", err))?;
+    }
+
+    if let Some(banner) = &monitor.banner {
+        let mut last = None;
+
+        loop {
+            let message = socket
+                .next()
+                .or(async {
+                    Timer::at(deadline).await;
+
+                    None
+                })
+                .await;
+
+            let reply = match message {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(Message::Binary(data))) => String::from_utf8_lossy(&data).to_string(),
+                Some(Ok(Message::Close(_))) | None => {
+                    return Err(match last {
+                        Some(last) => format!("expected reply {:?}, got {:?}", banner, last),
+                        None => format!("expected reply {:?}, got none", banner),
+                    });
+                }
+                // pings are answered while reading
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Err(format!("failed to read reply: {}", err)),
+            };
+
+            if reply.contains(banner.as_str()) {
+                break;
+            }
+
+            last = Some(reply.chars().take(MAX_REPLY_LENGTH).collect::<String>());
+        }
+
+        let replied = Instant::now();
+
+        *latency = Some(millis(start, replied));
+        *detail = Some(format!(
+            "handshake {} ms, round trip {} ms",
+            millis(start, handshake),
+            millis(handshake, replied)
+        ));
+    }
+
+    let _ = socket.close(None).await;
+
+    Ok(())
+}
+
+/// Open a WebSocket to a WebSocket monitor, sending its probe and waiting for its banner
+///
+/// `latency` is set to the time taken by the handshake and, if a banner is
+/// expected, the round trip until a message containing it is received. The
+/// check fails if it hasn't been received by `deadline`.
+pub async fn check(
+    monitor: &Monitor,
+    deadline: Instant,
+    latency: &mut Option<u128>,
+    detail: &mut Option<String>,
+) -> Result<(), String> {
+    let tls = uses_tls(&monitor.url)?;
+
+    let url = surf::Url::parse(&monitor.url).map_err(|err| err.to_string())?;
+    let host = url.host_str().unwrap_or_default().to_string();
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let mut request = monitor
+        .url
+        .as_str()
+        .into_client_request()
+        .map_err(|err| format!("invalid URL {}: {}", monitor.url, err))?;
+
+    for (name, value) in monitor.headers.iter().flatten() {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|err| format!("invalid header {}: {}", name, err))?;
+        let value = HeaderValue::from_str(&value.resolve()?)
+            .map_err(|err| format!("invalid value of header {}: {}", name, err))?;
+
+        request.headers_mut().insert(name, value);
+    }
+
+    let start = Instant::now();
+
+    let stream = TcpStream::connect((host.as_str(), port))
+        .await
+        .map_err(|err| format!("failed to connect to {}:{}: {}", host, port, err))?;
+
+    if tls {
+        let config = certificate::client_config(monitor).await?;
+
+        let stream = TlsConnector::from(Arc::new(config))
+            .connect(&host, stream)
+            .await
+            .map_err(|err| format!("TLS handshake with {} failed: {}", host, err))?;
+
+        exchange(monitor, request, stream, start, deadline, latency, detail).await
+    } else {
+        exchange(monitor, request, stream, start, deadline, latency, detail).await
+    }
+}
@@ -1,7 +1,27 @@
-use std::{collections::HashMap, fs::File, io::Read, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
-use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
+use surf::http::Method;
+
+use crate::{
+    assertion::{Assertions, Content, Pattern},
+    check::{
+        certificate,
+        dns::{self, RecordType},
+        flow::Step,
+        grpc, tcp, websocket,
+    },
+    error::{Result, VelocityError},
+    heartbeat,
+    provider::ComponentResponse,
+    request::{Auth, RequestBody, Secret},
+    state::{FlapDetection, LatencyThreshold},
+};
 
 /// Manages configuration variables
 /// All configuration details are specified in `velocity.toml`
@@ -22,54 +42,540 @@ pub struct Config {
     /// maximum connection timeout for all endpoints
     /// default: 30s
     pub max_connection_timeout: Option<u64>,
+    /// maximum number of endpoints checked at the same time
+    /// default: 10
+    pub max_concurrent_checks: Option<usize>,
     /// incident monitoring time, in number of requests
     /// when the number of requests specified above have been satisfied, we will mark the incident as resolved
     /// default: 60
     pub incident_monitoring_threshold: Option<u64>,
+    /// status page backend to report incidents and metrics to
+    /// default: instatus
+    pub provider: Option<Provider>,
+    /// base URL of the status page API
+    /// can be overridden with the `VELOCITY_API_BASE_URL` environment variable
+    /// default: https://api.instatus.com/v1
+    pub api_base_url: Option<String>,
+    /// address heartbeat monitors are pinged at, as `ip:port`
+    /// the listener is only started if there are heartbeat monitors
+    /// default: 0.0.0.0:8080
+    pub heartbeat_address: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Monitor {
+    /// endpoint to check
+    /// a URL for HTTP and gRPC monitors, a `ws://` or `wss://` URL for WebSocket monitors, `host:port` or `tcp://host:port` for TCP and reachability monitors,
+    /// the name to resolve for DNS monitors
+    /// `host`, `host:port` or an https URL for certificate monitors
+    /// and the path jobs ping for heartbeat monitors, such as `/backups`
+    /// not used by command and flow monitors
+    #[serde(default)]
     pub url: String,
     #[serde(rename = "type")]
     pub type_: MonitorType,
+    /// name of the metric latency is reported to
+    /// default: name of the monitor for latency, reachability and WebSocket monitors, none otherwise
+    pub metric: Option<String>,
+    /// frequency to check this endpoint, in seconds
+    /// default: `frequency` of the configuration
+    pub frequency: Option<u64>,
+    /// connection timeout for this endpoint, in seconds
+    /// default: `maxConnectionTimeout` of the configuration
+    pub timeout: Option<u64>,
+    /// number of times a failed check is retried before the endpoint is considered down
+    /// default: 0
+    pub retries: Option<u32>,
+    /// delay before the first retry of a failed check, in milliseconds, doubling with every retry
+    /// default: 500
+    pub retry_delay: Option<u64>,
+    /// timeout of retries, in seconds
+    /// default: `timeout` of the monitor
+    pub retry_timeout: Option<u64>,
+    /// upper bound of the random delay before the first check, in seconds
+    /// spreads out checks of monitors sharing the same frequency
+    /// default: 0
+    pub jitter: Option<u64>,
+    /// number of checks which have to fail in a row before an incident is opened
+    /// default: 1
+    pub failures_before_incident: Option<u32>,
+    /// number of checks which have to succeed in a row before an incident moves to monitoring
+    /// default: 1
+    pub successes_before_recovery: Option<u32>,
+    /// state changes within a number of checks after which the monitor is considered flapping
+    /// incidents are neither opened nor resolved while flapping, until the monitor stays up or down
+    /// for as many checks
+    /// example: `{ "changes": 4, "checks": 10 }`
+    pub flap_detection: Option<FlapDetection>,
+    /// IDs or names of the status page components affected when the endpoint fails
+    /// every entry has to match a component of the status page, or velocity won't start
+    /// components affected by several monitors have a partial outage while only some of them are down
+    /// default: the component named like the monitor, if any
+    pub components: Option<Vec<String>>,
+    /// latency above which the endpoint is reported as degraded, once exceeded for a number of checks in a row
+    /// latency monitors with a threshold open incidents like uptime monitors
+    /// example: `{ "warning": 500, "window": 3 }`
+    pub latency_threshold: Option<LatencyThreshold>,
+    /// conditions the response has to meet for the endpoint to be considered up
+    /// default: any 2xx status
+    pub assertions: Option<Assertions>,
+    /// keywords the response body of an HTTP monitor has to contain, and whether changes to it are reported
+    /// example: `{ "keywords": ["Add to cart"], "detectChanges": true, "ignore": "csrf=\\w+" }`
+    pub content: Option<Content>,
+    /// HTTP method of the request
+    /// default: GET
+    pub method: Option<Method>,
+    /// headers sent with the request or WebSocket handshake, values can be read from the environment
+    /// example: `{ "X-Health-Key": { "env": "HEALTH_KEY" } }`
+    pub headers: Option<BTreeMap<String, Secret>>,
+    /// body sent with the request, inline or read from a file
+    pub body: Option<RequestBody>,
+    /// credentials sent with the request
+    /// example: `{ "type": "bearer", "token": { "env": "API_TOKEN" } }`
+    pub auth: Option<Auth>,
+    /// payload sent once a TCP connection is open, sent as a text message by WebSocket monitors
+    /// example: `"PING\r\n"`
+    pub probe: Option<String>,
+    /// text a TCP endpoint has to respond with for it to be considered up
+    /// for WebSocket monitors, text a message has to contain, the round trip counting towards the latency
+    /// example: `"+PONG"`
+    pub banner: Option<String>,
+    /// type of the records a DNS monitor resolves
+    /// default: A
+    pub record_type: Option<RecordType>,
+    /// DNS server queried by a DNS monitor, as `ip` or `ip:port`
+    /// default: the first nameserver in `/etc/resolv.conf`
+    pub resolver: Option<String>,
+    /// answers a DNS monitor expects, each has to be among the records resolved
+    /// example: `["93.184.216.34"]` or `["10 mail.example.com"]` for MX records
+    pub answers: Option<Vec<String>>,
+    /// longest a DNS resolution may take before the monitor is considered down, in milliseconds
+    pub max_resolution_time: Option<u64>,
+    /// days before expiry a certificate is reported as degraded
+    /// default: 14
+    pub expiry_threshold: Option<u64>,
+    /// PEM file of certificate authorities trusted in addition to the usual roots
+    /// useful for endpoints using an internal certificate authority
+    pub ca_certificate: Option<PathBuf>,
+    /// number of TCP handshakes timed by a reachability monitor on every check
+    /// default: 5
+    pub attempts: Option<u32>,
+    /// percentage of handshakes which may fail before a reachability monitor is considered down
+    /// default: down only when every handshake fails
+    pub max_loss: Option<f64>,
+    /// longest a heartbeat monitor may go without a ping before it is considered down, in seconds
+    /// default: `frequency` of the monitor
+    pub grace_period: Option<u64>,
+    /// executable run by a command monitor, followed by its arguments
+    /// example: `["/usr/lib/nagios/plugins/check_disk", "-w", "20%", "-c", "10%"]`
+    pub command: Option<Vec<String>>,
+    /// regular expression the standard output of a command monitor has to match
+    pub output_matches: Option<Pattern>,
+    /// service whose health a gRPC monitor checks
+    /// default: the overall health of the server
+    pub service: Option<String>,
+    /// requests made in order by a flow monitor, each passing values on to the next
+    /// `headers` and `auth` of the monitor are sent with every step
+    /// example: `[{ "url": "https://example.com/login", "extract": { "token": { "json": "$.token" } } }]`
+    pub steps: Option<Vec<Step>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub enum MonitorType {
+    /// HTTP endpoint, opening an incident when it is down
     Uptime,
+    /// HTTP endpoint whose latency is reported to a metric
     Latency,
+    /// sequence of HTTP requests, opening an incident naming the step which failed
+    Flow,
+    /// TCP port, opening an incident when it can't be connected to
+    Tcp,
+    /// DNS name, opening an incident when it doesn't resolve as expected
+    Dns,
+    /// TLS certificate, degraded when close to expiry and down when invalid
+    Certificate,
+    /// gRPC server implementing the standard health checking protocol, over TLS for https URLs
+    /// down when NOT_SERVING and degraded when its health is UNKNOWN
+    Grpc,
+    /// local executable, down when it exits with a non-zero status
+    /// exit status 1 is reported as degraded performance, like a Nagios warning
+    Command,
+    /// job pinging velocity, opening an incident when it stops checking in
+    Heartbeat,
+    /// TCP port whose handshake time is reported to a metric, opening an incident when unreachable
+    Reachability,
+    /// WebSocket whose handshake and round-trip time is reported to a metric,
+    /// opening an incident when the upgrade fails or the expected reply doesn't arrive
+    #[serde(rename = "websocket")]
+    WebSocket,
+}
+
+impl MonitorType {
+    /// Whether failed checks open an incident on the status page
+    pub fn opens_incidents(&self) -> bool {
+        !matches!(self, Self::Latency)
+    }
+}
+
+impl Monitor {
+    /// Whether failed checks of this monitor open an incident on the status page
+    pub fn opens_incidents(&self) -> bool {
+        self.type_.opens_incidents() || self.latency_threshold.is_some()
+    }
+
+    /// Whether `component` is affected by the monitor named `name`
+    ///
+    /// Explicit components match by ID or name, otherwise the component has
+    /// to be named like the monitor.
+    pub fn affects(&self, name: &str, component: &ComponentResponse) -> bool {
+        match &self.components {
+            Some(components) => components
+                .iter()
+                .any(|entry| *entry == component.id || *entry == component.name),
+            None => component.name == name,
+        }
+    }
+
+    /// Name of the metric the latency of the monitor named `name` is reported to, if any
+    pub fn metric_name<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        match (&self.metric, &self.type_) {
+            (Some(metric), _) => Some(metric),
+            (None, MonitorType::Latency | MonitorType::Reachability | MonitorType::WebSocket) => {
+                Some(name)
+            }
+            (None, _) => None,
+        }
+    }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum Provider {
+    #[default]
+    Instatus,
+}
+
+/// Default base URL of the Instatus API
+pub const DEFAULT_API_BASE_URL: &str = "https://api.instatus.com/v1";
+
+/// Timeout of requests to the status page API and of checks, in seconds, unless configured otherwise
+pub const DEFAULT_CONNECTION_TIMEOUT: u64 = 30;
+
+/// Number of endpoints checked at the same time, unless configured otherwise
+pub const DEFAULT_CONCURRENT_CHECKS: usize = 10;
+
+/// Number of successful checks an incident is monitored for before it is resolved, unless configured otherwise
+pub const DEFAULT_INCIDENT_MONITORING_THRESHOLD: u64 = 60;
+
+/// Environment variable used to override `apiBaseUrl`
+pub const API_BASE_URL_ENV: &str = "VELOCITY_API_BASE_URL";
+
 impl Config {
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Self {
-        let mut file = File::open(path).unwrap_or_else(|err| {
-            eprintln!("\n💥 failed to open config file: {}", err.bright_yellow());
-            std::process::exit(1);
-        });
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let read_error = |source| VelocityError::ReadConfig {
+            path: path.as_ref().to_path_buf(),
+            source,
+        };
+
+        let mut file = File::open(&path).map_err(read_error)?;
 
         let mut contents = String::new();
 
-        file.read_to_string(&mut contents).unwrap_or_else(|err| {
-            eprintln!("\n💥 failed to read config file: {}", err.bright_yellow());
-            std::process::exit(1);
-        });
+        file.read_to_string(&mut contents).map_err(read_error)?;
 
-        let mut config = serde_json::from_str::<Config>(&contents).unwrap_or_else(|err| {
-            eprintln!("\n💥 invalid configuration file: {}\n\nTo learn more about velocity configuration see https://hydralite.io/velocity/docs/configuration", err.bright_yellow());
-            std::process::exit(1);
-        });
+        Self::from_json(&contents)
+    }
+
+    /// Parse a configuration from its JSON representation, filling in defaults
+    pub fn from_json(contents: &str) -> Result<Self> {
+        let mut config = serde_json::from_str::<Config>(contents)?;
 
         if config.max_connection_timeout.is_none() {
-            config.max_connection_timeout = Some(30);
+            config.max_connection_timeout = Some(DEFAULT_CONNECTION_TIMEOUT);
+        }
+
+        if config.max_concurrent_checks.is_none() {
+            config.max_concurrent_checks = Some(DEFAULT_CONCURRENT_CHECKS);
         }
 
         if config.incident_monitoring_threshold.is_none() {
-            config.incident_monitoring_threshold = Some(60);
+            config.incident_monitoring_threshold = Some(DEFAULT_INCIDENT_MONITORING_THRESHOLD);
+        }
+
+        for monitor in config.monitors.values_mut() {
+            if monitor.frequency.is_none() {
+                monitor.frequency = Some(config.frequency);
+            }
+
+            if monitor.timeout.is_none() {
+                monitor.timeout = config.max_connection_timeout;
+            }
+
+            if monitor.jitter.is_none() {
+                monitor.jitter = Some(0);
+            }
+        }
+
+        if config.provider.is_none() {
+            config.provider = Some(Provider::Instatus);
+        }
+
+        if let Ok(url) = std::env::var(API_BASE_URL_ENV) {
+            config.api_base_url = Some(url);
+        }
+
+        if config.api_base_url.is_none() {
+            config.api_base_url = Some(DEFAULT_API_BASE_URL.to_string());
+        }
+
+        Ok(config)
+    }
+
+    /// Find likely mistakes in a parsed configuration
+    ///
+    /// These don't stop velocity from running, but usually mean it won't
+    /// behave as intended. Each warning is a human readable sentence.
+    pub fn lint(&self) -> Vec<String> {
+        let mut warnings = vec![];
+
+        if self.api_key.trim().is_empty() {
+            warnings.push("apiKey is empty".to_string());
+        }
+
+        if self.monitors.is_empty() {
+            warnings.push("no monitors are configured".to_string());
+        }
+
+        if self.frequency == 0 {
+            warnings.push("frequency is 0, endpoints would be checked continuously".to_string());
+        }
+
+        if self.max_concurrent_checks == Some(0) {
+            warnings.push("maxConcurrentChecks is 0, checks will run one at a time".to_string());
+        }
+
+        if let Some(url) = &self.api_base_url {
+            if surf::Url::parse(url).is_err() {
+                warnings.push(format!("apiBaseUrl {} is not a valid URL", url));
+            }
+        }
+
+        let mut names: Vec<&String> = self.monitors.keys().collect();
+        names.sort();
+
+        // names of the heartbeat monitors, by path
+        let mut heartbeats: HashMap<String, &String> = HashMap::new();
+
+        for name in names {
+            let monitor = &self.monitors[name];
+
+            match monitor.type_ {
+                MonitorType::Uptime | MonitorType::Latency => {
+                    match surf::Url::parse(&monitor.url) {
+                        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+                        Ok(url) => warnings.push(format!(
+                            "{}: unsupported URL scheme {}, expected http or https",
+                            name,
+                            url.scheme()
+                        )),
+                        Err(err) => {
+                            warnings.push(format!("{}: invalid URL {}: {}", name, monitor.url, err))
+                        }
+                    }
+                }
+                MonitorType::Flow => match monitor.steps.as_deref() {
+                    Some(steps) if !steps.is_empty() => {
+                        let mut defined = vec![];
+
+                        for (index, step) in steps.iter().enumerate() {
+                            for variable in step.variables() {
+                                if !defined.contains(&variable) {
+                                    warnings.push(format!(
+                                        "{}: {} uses {{{{{}}}}}, which no earlier step extracts",
+                                        name,
+                                        step.label(index + 1),
+                                        variable
+                                    ));
+                                }
+                            }
+
+                            defined.extend(step.extract.iter().flatten().map(|(v, _)| v.as_str()));
+                        }
+                    }
+                    _ => warnings.push(format!("{}: no steps are configured", name)),
+                },
+                MonitorType::Tcp => {
+                    if let Err(err) = tcp::address(&monitor.url) {
+                        warnings.push(format!("{}: {}", name, err));
+                    }
+                }
+                MonitorType::Grpc => {
+                    if let Err(err) = grpc::uses_tls(&monitor.url) {
+                        warnings.push(format!("{}: {}", name, err));
+                    }
+                }
+                MonitorType::WebSocket => {
+                    if let Err(err) = websocket::uses_tls(&monitor.url) {
+                        warnings.push(format!("{}: {}", name, err));
+                    }
+                }
+                MonitorType::Command => {
+                    if monitor.command.as_ref().is_none_or(Vec::is_empty) {
+                        warnings.push(format!("{}: no command is configured", name));
+                    }
+                }
+                MonitorType::Heartbeat => {
+                    let path = heartbeat::path(&monitor.url);
+
+                    if let Some(other) = heartbeats.insert(path.clone(), name) {
+                        warnings.push(format!(
+                            "{}: heartbeat path {} is also used by {}",
+                            name, path, other
+                        ));
+                    }
+
+                    if monitor.grace_period == Some(0) {
+                        warnings.push(format!(
+                            "{}: gracePeriod is 0, the monitor will be down between pings",
+                            name
+                        ));
+                    }
+                }
+                MonitorType::Reachability => {
+                    if let Err(err) = tcp::address(&monitor.url) {
+                        warnings.push(format!("{}: {}", name, err));
+                    }
+
+                    if monitor.attempts == Some(0) {
+                        warnings.push(format!(
+                            "{}: attempts is 0, a single handshake will be timed",
+                            name
+                        ));
+                    }
+
+                    if let Some(max_loss) = monitor.max_loss {
+                        if !(0.0..=100.0).contains(&max_loss) {
+                            warnings.push(format!(
+                                "{}: maxLoss of {}% is not between 0 and 100",
+                                name, max_loss
+                            ));
+                        }
+                    }
+                }
+                MonitorType::Dns => {
+                    if let Err(err) = simple_dns::Name::new(&monitor.url) {
+                        warnings.push(format!("{}: invalid name {}: {}", name, monitor.url, err));
+                    }
+
+                    if let Some(Err(err)) = monitor.resolver.as_deref().map(dns::resolver_address) {
+                        warnings.push(format!("{}: {}", name, err));
+                    }
+                }
+                MonitorType::Certificate => {
+                    if let Err(err) = certificate::target(&monitor.url) {
+                        warnings.push(format!("{}: {}", name, err));
+                    }
+                }
+            }
+
+            if monitor.content.is_some()
+                && !matches!(monitor.type_, MonitorType::Uptime | MonitorType::Latency)
+            {
+                warnings.push(format!(
+                    "{}: content is only checked by uptime and latency monitors",
+                    name
+                ));
+            }
+
+            if monitor.failures_before_incident == Some(0) {
+                warnings.push(format!(
+                    "{}: failuresBeforeIncident is 0, a single failure opens an incident",
+                    name
+                ));
+            }
+
+            if monitor.successes_before_recovery == Some(0) {
+                warnings.push(format!(
+                    "{}: successesBeforeRecovery is 0, a single success starts recovery",
+                    name
+                ));
+            }
+
+            if let Some(LatencyThreshold {
+                window: Some(0), ..
+            }) = monitor.latency_threshold
+            {
+                warnings.push(format!(
+                    "{}: latencyThreshold.window is 0, a single slow check is reported as degraded",
+                    name
+                ));
+            }
+
+            if let Some(FlapDetection { changes, checks }) = monitor.flap_detection {
+                if changes == 0 {
+                    warnings.push(format!(
+                        "{}: flapDetection.changes is 0, the first change would count as flapping",
+                        name
+                    ));
+                } else if changes >= checks {
+                    warnings.push(format!(
+                        "{}: flapDetection can never detect flapping, {} checks change state at most {} times",
+                        name,
+                        checks,
+                        checks.saturating_sub(1)
+                    ));
+                }
+            }
+
+            let frequency = monitor.frequency.unwrap_or(self.frequency);
+
+            if monitor.frequency == Some(0) {
+                warnings.push(format!(
+                    "{}: frequency is 0, the endpoint would be checked continuously",
+                    name
+                ));
+            }
+
+            for secret in monitor.secrets() {
+                if let Some(env) = secret.env_var() {
+                    if std::env::var(env).is_err() {
+                        warnings.push(format!("{}: environment variable {} is not set", name, env));
+                    }
+                }
+            }
+
+            if let Some(RequestBody::File { file }) = &monitor.body {
+                if !file.is_file() {
+                    warnings.push(format!(
+                        "{}: body file {} does not exist",
+                        name,
+                        file.display()
+                    ));
+                }
+            }
+
+            if let Some(path) = &monitor.ca_certificate {
+                if !path.is_file() {
+                    warnings.push(format!(
+                        "{}: CA certificate {} does not exist",
+                        name,
+                        path.display()
+                    ));
+                }
+            }
+
+            if let Some(jitter) = monitor.jitter {
+                if jitter > 0 && jitter >= frequency {
+                    warnings.push(format!(
+                        "{}: jitter of {}s is not shorter than its frequency of {}s",
+                        name, jitter, frequency
+                    ));
+                }
+            }
         }
 
-        config
+        warnings
     }
 }
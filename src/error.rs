@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use surf::StatusCode;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, VelocityError>;
+
+/// Errors which can occur while setting up or running velocity
+#[derive(Debug, Error)]
+pub enum VelocityError {
+    /// the configuration file could not be opened or read
+    #[error("failed to read config file {path}: {source}")]
+    ReadConfig {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// the configuration file is not valid
+    #[error("invalid configuration file: {0}")]
+    InvalidConfig(#[from] serde_json::Error),
+    /// the network client could not be initialised
+    #[error("failed to initialise network client: {0}")]
+    Client(String),
+    /// no status page matches the name in the configuration
+    #[error("could not find relevant status page with name {0}")]
+    PageNotFound(String),
+    /// a latency monitor has no metric to report to
+    #[error("could not detect any metrics corresponding to {0}")]
+    MissingMetric(String),
+    /// components listed by monitors don't exist on the status page, as `monitor: component`
+    #[error("could not find components {}", .0.join(", "))]
+    MissingComponents(Vec<String>),
+    /// a request to the status page API failed
+    #[error("{action}: {error}")]
+    Api { action: String, error: surf::Error },
+    /// the listener receiving heartbeats could not be started
+    #[error("failed to listen for heartbeats on {address}: {source}")]
+    Heartbeat {
+        address: String,
+        source: std::io::Error,
+    },
+    /// signal handlers could not be installed
+    #[error("failed to listen for shutdown signals: {0}")]
+    Signal(#[from] ctrlc::Error),
+}
+
+impl VelocityError {
+    /// Wrap an error returned by the status page API
+    pub fn api(action: impl Into<String>, error: surf::Error) -> Self {
+        Self::Api {
+            action: action.into(),
+            error,
+        }
+    }
+
+    /// Whether the error can't be recovered from by retrying
+    ///
+    /// Fatal errors are caused by the configuration, such as an invalid API
+    /// key, and will keep occurring until it is fixed.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            Self::Api { error, .. } => {
+                matches!(
+                    error.status(),
+                    StatusCode::Unauthorized | StatusCode::Forbidden
+                )
+            }
+            _ => true,
+        }
+    }
+}
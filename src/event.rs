@@ -0,0 +1,71 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use crate::check::CheckResult;
+
+/// Something that happened while monitoring
+///
+/// Events are passed to the handler registered with
+/// [`Velocity::on_event`](crate::velocity::Velocity::on_event), which is how
+/// the binary reports progress. Nothing is printed by the library itself.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// pre-flight setup is connecting to the status page API at `host`
+    Connecting { host: String },
+    /// monitoring has started
+    Started,
+    /// the listener receiving heartbeats has started
+    HeartbeatListening { address: SocketAddr },
+    /// a heartbeat monitor has pinged
+    HeartbeatReceived { name: String },
+    /// a monitor has been checked
+    Checked(Box<CheckResult>),
+    /// a monitor has changed between up and down `changes` times within its last `checks` checks,
+    /// incidents are held back until it is stable
+    FlappingStarted {
+        name: String,
+        changes: u32,
+        checks: u32,
+    },
+    /// a flapping monitor has kept the same state for as many checks as flapping is detected in
+    FlappingStopped { name: String },
+    /// the latency of a monitor was pushed to its metric
+    LatencyReported {
+        name: String,
+        /// measured latency, in milliseconds
+        latency: u128,
+        /// how long pushing the metric point took
+        elapsed: Duration,
+    },
+    /// an incident was opened for a failing monitor
+    IncidentOpened { name: String, elapsed: Duration },
+    /// the status of the components affected by an open incident changed with the share of failing monitors
+    IncidentUpdated {
+        name: String,
+        incident_id: String,
+        /// name and new status of every component of the incident
+        statuses: Vec<(String, String)>,
+    },
+    /// an incident could not be opened for a failing monitor
+    IncidentFailed {
+        name: String,
+        elapsed: Duration,
+        error: String,
+    },
+    /// an incident has been moved to monitoring after a successful check
+    IncidentMonitoring { name: String, incident_id: String },
+    /// an incident has been resolved after a successful check
+    IncidentResolved {
+        name: String,
+        incident_id: String,
+        elapsed: Duration,
+    },
+    /// a recoverable error occurred, monitoring continues
+    Error { message: String },
+    /// the incident list couldn't be fetched, due checks are retried after `delay`
+    Retrying { delay: Duration },
+    /// a shutdown was requested, waiting for checks still in flight
+    ShuttingDown { in_flight: usize },
+}
+
+/// Callback receiving every [`Event`]
+pub type EventHandler = Arc<dyn Fn(&Event) + Send + Sync>;
@@ -0,0 +1,161 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use smol::{
+    net::{TcpListener, TcpStream},
+    Timer,
+};
+use surf::http::{Response, StatusCode};
+
+use crate::{
+    check::{CheckResult, Severity},
+    config::{Config, Monitor, MonitorType},
+    event::{Event, EventHandler},
+};
+
+/// Address the heartbeat listener binds to, unless configured otherwise
+pub const DEFAULT_HEARTBEAT_ADDRESS: &str = "0.0.0.0:8080";
+
+/// Delay before accepting connections again after accepting one failed
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Normalise the path of a heartbeat monitor to `/path`, without a trailing slash
+pub fn path(url: &str) -> String {
+    format!("/{}", url.trim_matches('/'))
+}
+
+/// Time of the last ping received by every heartbeat monitor
+///
+/// Cloning is cheap, clones share the same pings.
+#[derive(Clone, Default)]
+pub struct Heartbeats {
+    /// monitor name and time of the last ping, by path
+    pings: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+    /// frequency of the configuration, in seconds, for monitors without a grace period or frequency of their own
+    frequency: u64,
+}
+
+impl Heartbeats {
+    /// Track the heartbeat monitors of `config`, as if they had all just pinged
+    pub fn new(config: &Config) -> Self {
+        let now = Instant::now();
+
+        let pings = config
+            .monitors
+            .iter()
+            .filter(|(_, monitor)| matches!(monitor.type_, MonitorType::Heartbeat))
+            .map(|(name, monitor)| (path(&monitor.url), (name.clone(), now)))
+            .collect();
+
+        Self {
+            pings: Arc::new(Mutex::new(pings)),
+            frequency: config.frequency,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pings.lock().unwrap().is_empty()
+    }
+
+    /// Record a ping to `path`, returning the name of the monitor it belongs to
+    pub fn ping(&self, path: &str) -> Option<String> {
+        let mut pings = self.pings.lock().unwrap();
+
+        let (name, last) = pings.get_mut(&self::path(path))?;
+
+        *last = Instant::now();
+
+        Some(name.clone())
+    }
+
+    /// Check a heartbeat monitor, failing if it hasn't pinged within its grace period
+    pub fn check(&self, name: String, monitor: Monitor) -> CheckResult {
+        let last = self
+            .pings
+            .lock()
+            .unwrap()
+            .get(&path(&monitor.url))
+            .map(|(_, last)| last.elapsed());
+
+        let grace = Duration::from_secs(
+            monitor
+                .grace_period
+                .or(monitor.frequency)
+                .unwrap_or(self.frequency),
+        );
+
+        let failure = match last {
+            Some(last) if last <= grace => None,
+            Some(last) => Some(format!(
+                "no heartbeat received for {}s, longer than the grace period of {}s",
+                last.as_secs(),
+                grace.as_secs()
+            )),
+            None => Some(format!(
+                "no heartbeat is expected at {}",
+                path(&monitor.url)
+            )),
+        };
+
+        CheckResult {
+            name,
+            monitor,
+            latency: 0,
+            failure,
+            severity: Severity::Outage,
+            detail: last.map(|last| format!("last heartbeat {}s ago", last.as_secs())),
+            content_hash: None,
+            step: None,
+            attempts: 1,
+        }
+    }
+}
+
+/// Accept pings to heartbeat monitors on `listener` until the returned future is dropped
+///
+/// Any request to the path of a heartbeat monitor counts as a ping, other
+/// paths are answered with a 404.
+pub async fn serve(listener: TcpListener, heartbeats: Heartbeats, handler: Option<EventHandler>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                // errors such as running out of file descriptors persist for a while, so don't spin on them
+                tracing::warn!(error = %err, "failed to accept heartbeat connection");
+
+                Timer::after(ACCEPT_RETRY_DELAY).await;
+
+                continue;
+            }
+        };
+
+        smol::spawn(handle(stream, heartbeats.clone(), handler.clone())).detach();
+    }
+}
+
+async fn handle(stream: TcpStream, heartbeats: Heartbeats, handler: Option<EventHandler>) {
+    let _ = async_h1::accept(stream, |req| {
+        let name = heartbeats.ping(req.url().path());
+        let handler = handler.clone();
+
+        async move {
+            match name {
+                Some(name) => {
+                    if let Some(handler) = handler {
+                        handler(&Event::HeartbeatReceived { name });
+                    }
+
+                    let mut res = Response::new(StatusCode::Ok);
+                    res.set_body("OK");
+
+                    Ok(res)
+                }
+                None => Ok(Response::new(StatusCode::NotFound)),
+            }
+        }
+    })
+    .await;
+}
@@ -0,0 +1,27 @@
+//! Monitor endpoints and report their uptime and latency to a status page
+//!
+//! Connect to the status page with [`Velocity::connect`], register a
+//! callback with [`Velocity::on_event`] to follow check results and incidents,
+//! then run the monitor loop with [`Velocity::run`] until a [`Shutdown`] is
+//! triggered.
+
+pub mod assertion;
+pub mod check;
+pub mod config;
+pub mod error;
+pub mod event;
+pub mod heartbeat;
+pub mod net;
+pub mod provider;
+pub mod request;
+pub mod shutdown;
+pub mod state;
+pub mod velocity;
+
+pub use check::{check_all, check_monitor, CheckResult};
+pub use config::{Config, Monitor, MonitorType};
+pub use error::{Result, VelocityError};
+pub use event::{Event, EventHandler};
+pub use provider::{InstatusProvider, StatusPageProvider};
+pub use shutdown::Shutdown;
+pub use velocity::{Summary, Velocity};
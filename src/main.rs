@@ -1,15 +1,48 @@
-use std::time::Duration;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use config::Config;
+use chrono::Local;
+use clap::{Parser, Subcommand};
+use indicatif::{ProgressBar, ProgressStyle};
 use owo_colors::OwoColorize;
-use surf::Client;
 
 use tracing::Level;
 use tracing_subscriber::EnvFilter;
+use velocity::{
+    check::Severity, check_all, config::DEFAULT_CONNECTION_TIMEOUT, net, provider, CheckResult,
+    Config, Event, MonitorType, Result, Shutdown, StatusPageProvider, Velocity, VelocityError,
+};
 
-pub mod config;
-pub mod net;
-pub mod velocity;
+const MAX_MS_TIME: usize = 6;
+
+/// Monitor endpoints and report their uptime and latency to a status page
+#[derive(Parser)]
+#[clap(name = "velocity", version, about)]
+struct Cli {
+    /// Path to the configuration file
+    #[clap(long, short, global = true, default_value = "velocity.json")]
+    config: PathBuf,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Monitor endpoints and report incidents until interrupted (default)
+    Run,
+    /// Check every monitor once and print the results, failing if any are down
+    Check,
+    /// Parse the configuration and report likely mistakes, failing if any are found
+    Validate,
+    /// List the status pages the API key has access to
+    Pages,
+    /// List the components of the configured status page
+    Components,
+}
 
 fn main() {
     tracing_subscriber::fmt()
@@ -20,31 +53,390 @@ fn main() {
         .without_time()
         .init();
 
-    smol::block_on(async {
+    let cli = Cli::parse();
+
+    let result = smol::block_on(async {
+        match cli.command.unwrap_or(Command::Run) {
+            Command::Run => run(&cli.config).await.map(|_| true),
+            Command::Check => check(&cli.config).await,
+            Command::Validate => validate(&cli.config),
+            Command::Pages => pages(&cli.config).await.map(|_| true),
+            Command::Components => components(&cli.config).await.map(|_| true),
+        }
+    });
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("\n💥 {}", err.bright_yellow());
+
+            match err {
+                VelocityError::InvalidConfig(_) => eprintln!("\nTo learn more about velocity configuration see https://hydralite.io/velocity/docs/configuration"),
+                VelocityError::MissingMetric(_) => eprintln!("\nTo learn how to setup a metric, see https://hydralite.io/velocity/docs/metrics"),
+                VelocityError::MissingComponents(_) => eprintln!("\nTo list the IDs and names of the components of the status page, run velocity components"),
+                _ => {}
+            }
+
+            std::process::exit(1);
+        }
+    }
+}
+
+fn read_config(path: &Path) -> Result<Config> {
+    println!(
+        "📖 Reading configuration variables from {}",
+        path.display().bright_magenta()
+    );
+
+    Config::from_file(path)
+}
+
+/// Build a client for the status page API selected in `config`
+fn api_provider(config: &Config) -> Result<Arc<dyn StatusPageProvider>> {
+    Ok(provider::from_config(
+        config,
+        net::build_client(Some(Duration::from_secs(
+            config
+                .max_connection_timeout
+                .unwrap_or(DEFAULT_CONNECTION_TIMEOUT),
+        )))?,
+    ))
+}
+
+async fn run(path: &Path) -> Result<()> {
+    let config = read_config(path)?;
+
+    println!("✈️  Running {} setup...", "pre-flight".bright_cyan());
+
+    let bar = ProgressBar::new(2).with_style(
+        ProgressStyle::default_bar()
+            .template("{msg}")
+            .progress_chars("██"),
+    );
+
+    let progress = bar.clone();
+
+    let connected = Velocity::connect_with_events(config, move |event| match event {
+        Event::Connecting { host } => progress.set_message(format!(
+            "> {} {}",
+            "🔗".bright_yellow(),
+            host.bright_green().underline()
+        )),
+        event => print_event(event),
+    })
+    .await;
+
+    let velocity = match connected {
+        Ok(velocity) => {
+            bar.finish_with_message("✅ All checks passed");
+
+            velocity
+        }
+        Err(err) => {
+            bar.abandon_with_message(match &err {
+                VelocityError::PageNotFound(name) => {
+                    format!("❌  Could not find relevant status page with name {}", name)
+                }
+                err => format!("💥 pre-flight checks failed: {}", err),
+            });
+
+            return Err(err);
+        }
+    };
+
+    let shutdown = Shutdown::new();
+
+    shutdown.listen_for_signals()?;
+
+    let summary = velocity.run(shutdown).await?;
+
+    let _ = std::io::stdout().flush();
+
+    println!(
+        "👋 Monitored for {}s: {} checks, {} failed, {} incidents opened, {} resolved",
+        summary.uptime.as_secs(),
+        summary.checks.bright_cyan(),
+        summary.failures.bright_red(),
+        summary.incidents_opened.bright_yellow(),
+        summary.incidents_resolved.bright_green(),
+    );
+
+    Ok(())
+}
+
+async fn check(path: &Path) -> Result<bool> {
+    let config = read_config(path)?;
+
+    let heartbeats = config
+        .monitors
+        .values()
+        .filter(|monitor| matches!(monitor.type_, MonitorType::Heartbeat))
+        .count();
+
+    println!(
+        "🔍 Checking {} monitors...",
+        config.monitors.len() - heartbeats
+    );
+
+    if heartbeats > 0 {
         println!(
-            "📖 Reading configuration variables from {}",
-            "velocity.json".bright_magenta()
+            "💓 Skipping {} heartbeat monitors, heartbeats are only received while monitoring",
+            heartbeats
         );
+    }
 
-        let config = Config::from_file("velocity.json");
+    let results = check_all(&net::build_client(None)?, &config).await;
 
-        println!("✈️  Running {} setup...", "pre-flight".bright_cyan());
+    let failures = results.iter().filter(|result| !result.is_success()).count();
 
-        let (metrics, components, page) = net::pre_flight_setup(&config).await;
+    for result in results {
+        let latency = format!("{} ms", result.latency);
+        let spacing = spacing(result.latency);
 
-        println!("🌊 Spinning up network client");
+        let detail = detail(&result);
 
-        let client: Client = surf::Config::new()
-            .set_timeout(Some(Duration::from_secs(
-                config.max_connection_timeout.unwrap() as u64,
-            )))
-            .try_into()
-            .unwrap_or_else(|err| {
-                eprintln!("\n💥 failed to initialise network client: {}", err);
+        match (result.failure, result.severity) {
+            (None, _) => println!(
+                "{}{}✅  {} is up{}",
+                latency.bright_black(),
+                spacing,
+                result.name.bright_green(),
+                detail.bright_black()
+            ),
+            (Some(reason), Severity::Degraded) => println!(
+                "{}{}⚠️   {} is degraded: {}{}",
+                latency.bright_black(),
+                spacing,
+                result.name.bright_yellow(),
+                reason,
+                detail.bright_black()
+            ),
+            (Some(reason), Severity::Outage) => println!(
+                "{}{}❌  {} is down: {}{}",
+                latency.bright_black(),
+                spacing,
+                result.name.bright_red(),
+                reason,
+                detail.bright_black()
+            ),
+        }
+    }
 
-                std::process::exit(1);
-            });
+    if failures > 0 {
+        println!("\n💥 {} monitors failed", failures.bright_red());
+    }
 
-        velocity::monitor(page, components, metrics, client, config).await;
-    });
+    Ok(failures == 0)
+}
+
+fn validate(path: &Path) -> Result<bool> {
+    let config = read_config(path)?;
+
+    let warnings = config.lint();
+
+    for warning in &warnings {
+        println!("⚠️   {}", warning.bright_yellow());
+    }
+
+    if warnings.is_empty() {
+        println!(
+            "✅ Configuration is valid, {} monitors configured",
+            config.monitors.len()
+        );
+    }
+
+    Ok(warnings.is_empty())
+}
+
+async fn pages(path: &Path) -> Result<()> {
+    let config = read_config(path)?;
+
+    let provider = api_provider(&config)?;
+
+    for page in provider.list_pages().await? {
+        println!(
+            "📄 {}  {}",
+            page.name.bright_green(),
+            page.id.bright_black()
+        );
+    }
+
+    Ok(())
+}
+
+async fn components(path: &Path) -> Result<()> {
+    let config = read_config(path)?;
+
+    let provider = api_provider(&config)?;
+
+    let page = net::find_page(&config, provider.as_ref()).await?;
+
+    for component in net::fetch_components(provider.as_ref(), &page.id).await? {
+        println!(
+            "🧩 {}  {}",
+            component.name.bright_green(),
+            component.id.bright_black()
+        );
+    }
+
+    Ok(())
+}
+
+/// Additional information measured by a check and the number of attempts
+/// it took if it was retried, formatted to follow a log line
+fn detail(result: &CheckResult) -> String {
+    let attempts = format!("{} attempts", result.attempts);
+
+    let details: Vec<&str> = result
+        .detail
+        .as_deref()
+        .into_iter()
+        .chain(Some(attempts.as_str()).filter(|_| result.attempts > 1))
+        .collect();
+
+    if details.is_empty() {
+        return String::new();
+    }
+
+    format!(" ({})", details.join(", "))
+}
+
+/// Right-align a duration in milliseconds to the width of the log column
+fn spacing(millis: u128) -> String {
+    " ".repeat(MAX_MS_TIME.saturating_sub(millis.to_string().len()))
+}
+
+fn print_event(event: &Event) {
+    let time = Local::now().format("%H:%M:%S");
+
+    match event {
+        // shown by the pre-flight progress bar
+        Event::Connecting { .. } => {}
+        Event::Started => println!("🔍 Monitoring requests..."),
+        Event::HeartbeatListening { address } => {
+            println!("💓 Listening for heartbeats on http://{}", address)
+        }
+        Event::HeartbeatReceived { name } => println!(
+            "{}  💓  Heartbeat received from {}",
+            time.bright_yellow(),
+            name.bright_green()
+        ),
+        Event::Checked(result) => {
+            let detail = detail(result);
+
+            match (result.monitor.opens_incidents(), result.is_success()) {
+                (true, true) => println!(
+                    "{}  {}{}✅  {} is up{}",
+                    time.bright_yellow(),
+                    format!("{} ms", result.latency).bright_black(),
+                    spacing(result.latency),
+                    result.name.bright_green(),
+                    detail.bright_black()
+                ),
+                (true, false) if result.severity == Severity::Degraded => println!(
+                    "{}  {}{}⚠️   {} is degraded{}",
+                    time.bright_yellow(),
+                    format!("{} ms", result.latency).bright_black(),
+                    spacing(result.latency),
+                    result.name.bright_yellow(),
+                    detail.bright_black()
+                ),
+                (true, false) => println!(
+                    "{}  {}{}❌  {} is down{}",
+                    time.bright_yellow(),
+                    format!("{} ms", result.latency).bright_black(),
+                    spacing(result.latency),
+                    result.name.bright_red(),
+                    detail.bright_black()
+                ),
+                (false, false) => println!(
+                    "{}  {}{}⚠️   Unable to measure latency for {}",
+                    time.bright_yellow(),
+                    format!("{} ms", result.latency).bright_black(),
+                    spacing(result.latency),
+                    result.name.bright_yellow()
+                ),
+                // reported once the metric point has been pushed
+                (false, true) => {}
+            }
+        }
+        Event::FlappingStarted {
+            name,
+            changes,
+            checks,
+        } => println!(
+            "{}  〰️   {} is flapping, {} state changes in the last {} checks",
+            time.bright_yellow(),
+            name.bright_yellow(),
+            changes,
+            checks
+        ),
+        Event::FlappingStopped { name } => println!(
+            "{}  〰️   {} has stopped flapping",
+            time.bright_yellow(),
+            name.bright_green()
+        ),
+        Event::LatencyReported {
+            name,
+            latency,
+            elapsed,
+        } => println!(
+            "{}  {}{}📡  {} latency updated to {}",
+            time.bright_yellow(),
+            format!("{} ms", elapsed.as_millis()).bright_black(),
+            spacing(elapsed.as_millis()),
+            name.bright_green(),
+            format!("{} ms", latency).bright_black(),
+        ),
+        Event::IncidentOpened { name, elapsed } => println!(
+            "{}  {}{}🎫  Successfully created incident for {} ",
+            time.bright_yellow(),
+            format!("{} ms", elapsed.as_millis()).bright_black(),
+            spacing(elapsed.as_millis()),
+            name.bright_green()
+        ),
+        Event::IncidentUpdated { name, statuses, .. } => println!(
+            "{}  🎫  Updated incident for {}: {}",
+            time.bright_yellow(),
+            name.bright_yellow(),
+            statuses
+                .iter()
+                .map(|(component, status)| format!("{} is {}", component, status))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Event::IncidentFailed {
+            name,
+            elapsed,
+            error,
+        } => println!(
+            "{}  {}{}❌  Failed to create incident for {}: {}",
+            time.bright_yellow(),
+            format!("{} ms", elapsed.as_millis()).bright_black(),
+            spacing(elapsed.as_millis()),
+            name.bright_red(),
+            error
+        ),
+        Event::IncidentMonitoring { .. } => {}
+        Event::IncidentResolved { name, elapsed, .. } => println!(
+            "{}  {}{}✅  {} marked as resolved",
+            time.bright_yellow(),
+            format!("{} ms", elapsed.as_millis()).bright_black(),
+            spacing(elapsed.as_millis()),
+            name.bright_green()
+        ),
+        Event::Error { message } => {
+            eprintln!("{}  ❌  {}", time.bright_yellow(), message.bright_red())
+        }
+        Event::Retrying { delay } => println!(
+            "{}  🔁  Retrying in {}s",
+            time.bright_yellow(),
+            delay.as_secs()
+        ),
+        Event::ShuttingDown { in_flight } => println!(
+            "🛑 Shutting down, waiting for {} in-flight checks...",
+            in_flight
+        ),
+    }
 }
@@ -1,129 +1,115 @@
-use std::{collections::HashMap, process::exit};
+use std::{collections::HashMap, time::Duration};
 
-use indicatif::{ProgressBar, ProgressStyle};
-use owo_colors::OwoColorize;
+use surf::Client;
 
 use crate::{
-    config::{Config, MonitorType},
-    velocity::{ComponentResponse, Metric, StatusPage},
+    config::Config,
+    error::{Result, VelocityError},
+    provider::{ComponentResponse, StatusPage, StatusPageProvider},
 };
 
+/// Build a network client, applying `timeout` to every request made with it
+pub fn build_client(timeout: Option<Duration>) -> Result<Client> {
+    surf::Config::new()
+        .set_timeout(timeout)
+        .try_into()
+        .map_err(|err| VelocityError::Client(format!("{:?}", err)))
+}
+
+/// Find the metrics monitors report their latency to, by monitor name
+///
+/// `metric_loggers` pairs the name of each monitor with the name of its metric.
 pub async fn fetch_metrics(
-    metric_loggers: Vec<&String>,
+    provider: &dyn StatusPageProvider,
+    metric_loggers: Vec<(&String, &str)>,
     page_id: &str,
-    api_key: &str,
-    bar: ProgressBar,
-) -> HashMap<String, String> {
-    let res: Vec<Metric> = surf::get(format!("https://api.instatus.com/v1/{}/metrics", page_id))
-        .header("Authorization", format!("Bearer {}", api_key))
-        .recv_json()
-        .await
-        .unwrap_or_else(|err| {
-            bar.abandon_with_message(format!("💥 could not connect to Instatus API: {}", err));
-
-            exit(1);
-        });
+) -> Result<HashMap<String, String>> {
+    let res = provider.list_metrics(page_id).await?;
 
     let mut metrics = HashMap::new();
 
-    for metric in res {
-        if metric_loggers.contains(&&metric.name) {
-            metrics.insert(metric.name, metric.id);
-        }
+    // every monitor needs a metric to report to
+    for (monitor, metric_name) in metric_loggers {
+        let metric = res
+            .iter()
+            .rfind(|metric| metric.name == metric_name)
+            .ok_or_else(|| VelocityError::MissingMetric(metric_name.to_string()))?;
+
+        metrics.insert(monitor.clone(), metric.id.clone());
     }
 
-    metrics
+    Ok(metrics)
 }
 
-pub async fn fetch_components(page_id: &str, api_key: &str) -> Vec<ComponentResponse> {
-    surf::get(format!(
-        "https://api.instatus.com/v1/{}/components",
-        page_id
-    ))
-    .header("Authorization", format!("Bearer {}", api_key))
-    .recv_json::<Vec<ComponentResponse>>()
-    .await
-    .unwrap_or_else(|err| {
-        eprintln!(
-            "\n💥 failed to fetch components from Instatus API: {}",
-            err.bright_yellow()
-        );
-
-        std::process::exit(1);
-    })
+pub async fn fetch_components(
+    provider: &dyn StatusPageProvider,
+    page_id: &str,
+) -> Result<Vec<ComponentResponse>> {
+    provider.list_components(page_id).await
 }
 
-pub async fn pre_flight_setup(
-    config: &Config,
-) -> (HashMap<String, String>, Vec<ComponentResponse>, StatusPage) {
-    let bar = ProgressBar::new(2).with_style(
-        ProgressStyle::default_bar()
-            .template("{msg}")
-            .progress_chars("██"),
-    );
+/// Check that every component listed by a monitor matches the ID or name of one of `components`
+///
+/// Monitors without components affect the component named like them, if
+/// there is one, so only explicit lists are checked.
+pub fn verify_components(config: &Config, components: &[ComponentResponse]) -> Result<()> {
+    let mut missing = vec![];
+
+    for (name, monitor) in &config.monitors {
+        for entry in monitor.components.iter().flatten() {
+            if !components
+                .iter()
+                .any(|component| *entry == component.id || *entry == component.name)
+            {
+                missing.push(format!("{}: {}", name, entry));
+            }
+        }
+    }
 
-    bar.set_message(format!(
-        "> {} {}",
-        "🔗".bright_yellow(),
-        "api.instatus.com".to_string().bright_green().underline()
-    ));
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        missing.sort();
 
-    let res: Vec<StatusPage> = surf::get("https://api.instatus.com/v1/pages".to_string())
-        .header("Authorization", format!("Bearer {}", config.api_key))
-        .recv_json()
-        .await
-        .unwrap_or_else(|err| {
-            bar.abandon_with_message(format!("💥 could not connect to Instatus API: {}", err));
+        Err(VelocityError::MissingComponents(missing))
+    }
+}
+
+/// Find the status page named in the configuration
+pub async fn find_page(config: &Config, provider: &dyn StatusPageProvider) -> Result<StatusPage> {
+    provider
+        .list_pages()
+        .await?
+        .into_iter()
+        .rfind(|page| config.name == page.name)
+        .ok_or_else(|| VelocityError::PageNotFound(config.name.clone()))
+}
 
-            exit(1);
-        });
+/// Find the configured status page and fetch the metrics and components monitors report to
+///
+/// Fails if a monitor lists a component the status page doesn't have.
+pub async fn pre_flight_setup(
+    config: &Config,
+    provider: &dyn StatusPageProvider,
+) -> Result<(HashMap<String, String>, Vec<ComponentResponse>, StatusPage)> {
+    let status_page = find_page(config, provider).await?;
 
-    let mut status_page: Option<StatusPage> = None;
+    let mut metric_loggers = vec![];
 
-    for page in res {
-        if config.name == page.name {
-            status_page = Some(page);
+    for (name, monitor) in &config.monitors {
+        if let Some(metric) = monitor.metric_name(name) {
+            metric_loggers.push((name, metric));
         }
     }
 
-    if status_page.is_some() {
-        bar.inc(1);
-
-        let status_page = status_page.unwrap();
-
-        let mut metric_loggers = vec![];
+    let (metrics, components) = tokio::join!(
+        fetch_metrics(provider, metric_loggers, &status_page.id),
+        fetch_components(provider, &status_page.id),
+    );
 
-        for monitor in &config.monitors {
-            if let MonitorType::Latency = monitor.1.type_ {
-                metric_loggers.push(monitor.0);
-            }
-        }
+    let components = components?;
 
-        bar.set_message(format!(
-            "> {} {}",
-            "🔗".bright_yellow(),
-            "api.instatus.com".to_string().bright_green().underline()
-        ));
-
-        let (metrics, components) = tokio::join!(
-            fetch_metrics(
-                metric_loggers,
-                &status_page.id,
-                &config.api_key,
-                bar.clone(),
-            ),
-            fetch_components(&status_page.id, &config.api_key),
-        );
-
-        bar.finish_with_message("✅ All checks passed");
-
-        (metrics, components, status_page)
-    } else {
-        bar.abandon_with_message(format!(
-            "❌  Could not find relevant status page with name {}",
-            config.name
-        ));
+    verify_components(config, &components)?;
 
-        std::process::exit(1);
-    }
+    Ok((metrics?, components, status_page))
 }
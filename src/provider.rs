@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use surf::Client;
+
+use crate::{
+    config::{Config, Provider, DEFAULT_API_BASE_URL},
+    error::Result,
+};
+
+pub mod instatus;
+
+pub use instatus::InstatusProvider;
+
+/// A status page object
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StatusPage {
+    /// ID of the status page
+    pub id: String,
+    /// Name of the status page
+    pub name: String,
+}
+
+/// A metric object
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Metric {
+    /// ID of the metric
+    pub id: String,
+    /// Name of the metric
+    pub name: String,
+}
+
+/// A component object
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ComponentResponse {
+    /// ID of the component
+    pub id: String,
+    /// Name of the component
+    pub name: String,
+}
+
+/// The status of a single component affected by an incident
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ComponentStatus {
+    /// ID of the component
+    pub id: String,
+    /// Status of the component, e.g. `MAJOROUTAGE` or `OPERATIONAL`
+    pub status: String,
+}
+
+/// An incident object
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Incident {
+    /// ID of the incident
+    pub id: String,
+    /// Time the incident was started at, as reported by the provider
+    pub started: String,
+    /// Status of the incident, e.g. `IDENTIFIED`, `MONITORING` or `RESOLVED`
+    pub status: String,
+    /// Components affected by the incident
+    pub components: Vec<ComponentResponse>,
+}
+
+/// An incident to be opened on the status page
+#[derive(Debug, Clone)]
+pub struct NewIncident {
+    pub name: String,
+    pub message: String,
+    /// IDs of the affected components
+    pub components: Vec<String>,
+    pub started: DateTime<Local>,
+    pub status: String,
+    /// whether subscribers of the status page should be notified
+    pub notify: bool,
+    pub statuses: Vec<ComponentStatus>,
+}
+
+/// An update to the status of an existing incident
+#[derive(Debug, Clone)]
+pub struct IncidentStatusUpdate {
+    pub message: String,
+    /// IDs of the affected components
+    pub components: Vec<String>,
+    pub started: String,
+    pub status: String,
+    /// whether subscribers of the status page should be notified
+    pub notify: bool,
+    pub statuses: Vec<ComponentStatus>,
+}
+
+/// A single data point of a metric
+#[derive(Debug, Clone, Copy)]
+pub struct MetricPoint {
+    /// unix timestamp of the data point, in milliseconds
+    pub timestamp: u64,
+    pub value: u128,
+}
+
+/// A status page backend velocity reports to
+///
+/// Every interaction velocity has with a status page goes through this trait,
+/// so new backends can be supported without touching the monitor loop.
+#[async_trait]
+pub trait StatusPageProvider: Send + Sync {
+    /// Host of the API, used when reporting progress
+    fn host(&self) -> String;
+
+    /// List all status pages the API key has access to
+    async fn list_pages(&self) -> Result<Vec<StatusPage>>;
+
+    /// List the components of a status page
+    async fn list_components(&self, page_id: &str) -> Result<Vec<ComponentResponse>>;
+
+    /// List the metrics of a status page
+    async fn list_metrics(&self, page_id: &str) -> Result<Vec<Metric>>;
+
+    /// List the incidents of a status page
+    async fn list_incidents(&self, page_id: &str) -> Result<Vec<Incident>>;
+
+    /// Open a new incident on a status page
+    async fn create_incident(&self, page_id: &str, incident: NewIncident) -> Result<()>;
+
+    /// Post a status update to an existing incident
+    async fn update_incident(
+        &self,
+        page_id: &str,
+        incident_id: &str,
+        update: IncidentStatusUpdate,
+    ) -> Result<()>;
+
+    /// Push a data point to a metric
+    async fn push_metric_point(
+        &self,
+        page_id: &str,
+        metric_id: &str,
+        point: MetricPoint,
+    ) -> Result<()>;
+}
+
+/// Build the status page provider selected in the configuration
+pub fn from_config(config: &Config, client: Client) -> Arc<dyn StatusPageProvider> {
+    match config.provider.unwrap_or_default() {
+        Provider::Instatus => Arc::new(InstatusProvider::new(
+            client,
+            config.api_key.clone(),
+            config
+                .api_base_url
+                .clone()
+                .unwrap_or_else(|| DEFAULT_API_BASE_URL.to_string()),
+        )),
+    }
+}
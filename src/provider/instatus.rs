@@ -0,0 +1,201 @@
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use surf::{Client, RequestBuilder, Response, Url};
+
+use crate::error::{Result, VelocityError};
+
+use super::{
+    ComponentResponse, ComponentStatus, Incident, IncidentStatusUpdate, Metric, MetricPoint,
+    NewIncident, StatusPage, StatusPageProvider,
+};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LatencyPost {
+    timestamp: u64,
+    value: u128,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IncidentPost {
+    name: String,
+    message: String,
+    components: Vec<String>,
+    started: String,
+    status: String,
+    notify: bool,
+    statuses: Vec<ComponentStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IncidentUpdate {
+    message: String,
+    components: Vec<String>,
+    started: String,
+    status: String,
+    notify: bool,
+    statuses: Vec<ComponentStatus>,
+}
+
+impl From<NewIncident> for IncidentPost {
+    fn from(incident: NewIncident) -> Self {
+        Self {
+            name: incident.name,
+            message: incident.message,
+            components: incident.components,
+            started: incident.started.format("%Y-%m-%d %H:%M:%S.%3f").to_string(),
+            status: incident.status,
+            notify: incident.notify,
+            statuses: incident.statuses,
+        }
+    }
+}
+
+impl From<IncidentStatusUpdate> for IncidentUpdate {
+    fn from(update: IncidentStatusUpdate) -> Self {
+        Self {
+            message: update.message,
+            components: update.components,
+            started: update.started,
+            status: update.status,
+            notify: update.notify,
+            statuses: update.statuses,
+        }
+    }
+}
+
+impl From<MetricPoint> for LatencyPost {
+    fn from(point: MetricPoint) -> Self {
+        Self {
+            timestamp: point.timestamp,
+            value: point.value,
+        }
+    }
+}
+
+/// [Instatus](https://instatus.com) status page provider
+pub struct InstatusProvider {
+    client: Client,
+    api_key: String,
+    base_url: String,
+}
+
+impl InstatusProvider {
+    pub fn new(client: Client, api_key: String, base_url: String) -> Self {
+        Self {
+            client,
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        request.header("Authorization", format!("Bearer {}", self.api_key))
+    }
+
+    /// Send a request, treating any non-2xx response as an error
+    async fn send(&self, request: RequestBuilder) -> surf::Result<Response> {
+        let res = self.client.send(self.authorize(request)).await?;
+
+        if res.status().is_success() {
+            Ok(res)
+        } else {
+            Err(surf::Error::from_str(
+                res.status(),
+                format!("Instatus API responded with {}", res.status()),
+            ))
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, action: &str) -> Result<T> {
+        let res = async {
+            self.send(surf::get(format!("{}{}", self.base_url, path)))
+                .await?
+                .body_json::<T>()
+                .await
+        };
+
+        res.await.map_err(|err| VelocityError::api(action, err))
+    }
+
+    async fn post<T: Serialize>(&self, path: &str, body: &T, action: &str) -> Result<()> {
+        let res = async {
+            let request = surf::post(format!("{}{}", self.base_url, path)).body_json(body)?;
+
+            self.send(request).await.map(|_| ())
+        };
+
+        res.await.map_err(|err| VelocityError::api(action, err))
+    }
+}
+
+#[async_trait]
+impl StatusPageProvider for InstatusProvider {
+    fn host(&self) -> String {
+        Url::parse(&self.base_url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or_else(|| self.base_url.clone())
+    }
+
+    async fn list_pages(&self) -> Result<Vec<StatusPage>> {
+        self.get("/pages", "failed to list status pages").await
+    }
+
+    async fn list_components(&self, page_id: &str) -> Result<Vec<ComponentResponse>> {
+        self.get(
+            &format!("/{}/components", page_id),
+            "failed to fetch components",
+        )
+        .await
+    }
+
+    async fn list_metrics(&self, page_id: &str) -> Result<Vec<Metric>> {
+        self.get(&format!("/{}/metrics", page_id), "failed to fetch metrics")
+            .await
+    }
+
+    async fn list_incidents(&self, page_id: &str) -> Result<Vec<Incident>> {
+        self.get(
+            &format!("/{}/incidents", page_id),
+            "failed to get list of incidents",
+        )
+        .await
+    }
+
+    async fn create_incident(&self, page_id: &str, incident: NewIncident) -> Result<()> {
+        self.post(
+            &format!("/{}/incidents", page_id),
+            &IncidentPost::from(incident),
+            "failed to create incident",
+        )
+        .await
+    }
+
+    async fn update_incident(
+        &self,
+        page_id: &str,
+        incident_id: &str,
+        update: IncidentStatusUpdate,
+    ) -> Result<()> {
+        self.post(
+            &format!("/{}/incidents/{}/incident-updates", page_id, incident_id),
+            &IncidentUpdate::from(update),
+            "failed to update incident status",
+        )
+        .await
+    }
+
+    async fn push_metric_point(
+        &self,
+        page_id: &str,
+        metric_id: &str,
+        point: MetricPoint,
+    ) -> Result<()> {
+        self.post(
+            &format!("/{}/metrics/{}", page_id, metric_id),
+            &LatencyPost::from(point),
+            "failed to update latency",
+        )
+        .await
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use surf::{
+    http::{auth::BasicAuth, Method},
+    Client, RequestBuilder,
+};
+
+use crate::config::Monitor;
+
+/// A value which can be read from an environment variable
+/// so secrets don't have to be stored in `velocity.json`
+///
+/// example: `"hunter2"` or `{ "env": "HEALTH_KEY" }`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Secret {
+    Value(String),
+    Env { env: String },
+}
+
+impl Secret {
+    /// Name of the environment variable the value is read from, if any
+    pub fn env_var(&self) -> Option<&str> {
+        match self {
+            Self::Value(_) => None,
+            Self::Env { env } => Some(env),
+        }
+    }
+
+    pub fn resolve(&self) -> Result<String, String> {
+        match self {
+            Self::Value(value) => Ok(value.clone()),
+            Self::Env { env } => {
+                std::env::var(env).map_err(|_| format!("environment variable {} is not set", env))
+            }
+        }
+    }
+}
+
+/// Body sent with the request of a monitor
+///
+/// example: `"ping"`, `{ "json": { "deep": true } }` or `{ "file": "health.xml" }`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum RequestBody {
+    Text(String),
+    Json {
+        json: Value,
+    },
+    /// read from a file on every check, relative to the working directory
+    File {
+        file: PathBuf,
+    },
+}
+
+/// Credentials sent with the request of a monitor
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Auth {
+    /// `Authorization: Bearer <token>`
+    Bearer { token: Secret },
+    /// HTTP basic authentication
+    Basic { username: Secret, password: Secret },
+}
+
+impl Auth {
+    fn secrets(&self) -> Vec<&Secret> {
+        match self {
+            Self::Bearer { token } => vec![token],
+            Self::Basic { username, password } => vec![username, password],
+        }
+    }
+}
+
+impl Monitor {
+    /// Every secret used by the requests of this monitor, including the steps of a flow
+    pub fn secrets(&self) -> Vec<&Secret> {
+        let steps = self.steps.iter().flatten();
+
+        self.headers
+            .iter()
+            .chain(steps.clone().flat_map(|step| &step.headers))
+            .flatten()
+            .map(|(_, value)| value)
+            .chain(self.auth.iter().flat_map(Auth::secrets))
+            .chain(steps.flat_map(|step| &step.auth).flat_map(Auth::secrets))
+            .collect()
+    }
+}
+
+/// Build the request sent to check a monitor, resolving its secrets and body
+///
+/// Fails with a human readable reason if a secret or the body can't be read.
+pub async fn build(client: &Client, monitor: &Monitor) -> Result<RequestBuilder, String> {
+    let url = surf::Url::parse(&monitor.url)
+        .map_err(|err| format!("invalid URL {}: {}", monitor.url, err))?;
+
+    let mut request = client
+        .request(monitor.method.unwrap_or(Method::Get), url)
+        .header("Cache-Control", "no-cache, no-store, must-revalidate")
+        .header("Pragma", "no-cache")
+        .header("Expires", "0");
+
+    match &monitor.body {
+        Some(RequestBody::Text(text)) => request = request.body_string(text.clone()),
+        Some(RequestBody::Json { json }) => {
+            request = request
+                .body_json(json)
+                .map_err(|err| format!("invalid JSON body: {}", err))?
+        }
+        Some(RequestBody::File { file }) => {
+            let contents = smol::fs::read(file)
+                .await
+                .map_err(|err| format!("failed to read body from {}: {}", file.display(), err))?;
+
+            request = request.body_bytes(contents);
+        }
+        None => {}
+    }
+
+    match &monitor.auth {
+        Some(Auth::Bearer { token }) => {
+            request = request.header("Authorization", format!("Bearer {}", token.resolve()?));
+        }
+        Some(Auth::Basic { username, password }) => {
+            let auth = BasicAuth::new(username.resolve()?, password.resolve()?);
+
+            request = request.header(auth.name(), auth.value());
+        }
+        None => {}
+    }
+
+    // headers from the configuration take precedence over the defaults
+    for (name, value) in monitor.headers.iter().flatten() {
+        request = request.header(name.as_str(), value.resolve()?);
+    }
+
+    Ok(request)
+}
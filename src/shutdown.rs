@@ -0,0 +1,55 @@
+use smol::channel::{self, Receiver, Sender};
+
+/// Handle used to request a graceful shutdown of the monitor loop
+///
+/// Clones share the same state, so a shutdown triggered from any clone is
+/// observed by all of them.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    sender: Sender<()>,
+    receiver: Receiver<()>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (sender, receiver) = channel::bounded(1);
+
+        Self { sender, receiver }
+    }
+
+    /// Request a shutdown
+    pub fn trigger(&self) {
+        self.sender.close();
+    }
+
+    /// Whether a shutdown has been requested
+    pub fn is_triggered(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Wait until a shutdown is requested
+    pub async fn wait(&self) {
+        while self.receiver.recv().await.is_ok() {}
+    }
+
+    /// Request a shutdown on SIGINT or SIGTERM
+    ///
+    /// A second signal exits immediately, without waiting for in-flight checks.
+    pub fn listen_for_signals(&self) -> Result<(), ctrlc::Error> {
+        let shutdown = self.clone();
+
+        ctrlc::set_handler(move || {
+            if shutdown.is_triggered() {
+                std::process::exit(130);
+            }
+
+            shutdown.trigger();
+        })
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    check::{CheckResult, Failure, Severity},
+    config::Monitor,
+};
+
+/// Number of state changes within a number of checks which makes a monitor flap
+///
+/// example: `{ "changes": 4, "checks": 10 }`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FlapDetection {
+    /// changes between up and down, in either direction
+    pub changes: u32,
+    /// number of most recent checks the changes are counted in
+    pub checks: u32,
+}
+
+/// Latency above which checks are slow, and for how many checks in a row before degraded performance is reported
+///
+/// example: `{ "warning": 500, "window": 3 }`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyThreshold {
+    /// latency above which a check is slow, in milliseconds
+    pub warning: u64,
+    /// number of slow checks in a row reported as degraded performance
+    /// default: 1
+    pub window: Option<u32>,
+}
+
+/// A monitor starting or stopping to flap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flap {
+    /// the monitor changed state `changes` times within its last checks
+    Started { changes: u32 },
+    /// the monitor has kept the same state for as many checks as flapping is detected in
+    Stopped,
+}
+
+/// Outcome of the recent checks of a monitor, deciding when failures and
+/// recoveries are confirmed
+///
+/// A flapping monitor confirms neither, so incidents are neither opened nor
+/// resolved until it is stable again.
+#[derive(Debug, Clone, Default)]
+pub struct MonitorState {
+    /// number of checks which failed in a row
+    failures: u32,
+    /// number of checks which succeeded in a row
+    successes: u32,
+    /// number of successful checks in a row slower than the latency threshold
+    slow: u32,
+    /// whether the most recent checks succeeded, oldest first, for flap detection
+    history: VecDeque<bool>,
+    flapping: bool,
+}
+
+impl MonitorState {
+    /// Compare the latency of a successful check with the latency threshold of its monitor
+    ///
+    /// Returns the failure the check should be reported with once the
+    /// threshold has been exceeded for the whole window.
+    pub fn record_latency(&mut self, result: &CheckResult) -> Option<Failure> {
+        let threshold = result.monitor.latency_threshold?;
+
+        if !result.is_success() || result.latency <= u128::from(threshold.warning) {
+            self.slow = 0;
+
+            return None;
+        }
+
+        self.slow += 1;
+
+        let window = threshold.window.unwrap_or(1).max(1);
+
+        if self.slow < window {
+            return None;
+        }
+
+        Some(Failure::degraded(match window {
+            1 => format!(
+                "latency of {} ms is above the warning threshold of {} ms",
+                result.latency, threshold.warning
+            ),
+            _ => format!(
+                "latency of {} ms is above the warning threshold of {} ms for the last {} checks",
+                result.latency, threshold.warning, window
+            ),
+        }))
+    }
+
+    /// Record the outcome of a check, returning whether the monitor started or stopped flapping
+    pub fn record(&mut self, monitor: &Monitor, success: bool) -> Option<Flap> {
+        if success {
+            self.successes += 1;
+            self.failures = 0;
+        } else {
+            self.failures += 1;
+            self.successes = 0;
+        }
+
+        let detection = monitor.flap_detection?;
+
+        self.history.push_back(success);
+
+        while self.history.len() > detection.checks as usize {
+            self.history.pop_front();
+        }
+
+        let changes = self.changes();
+
+        match self.flapping {
+            false if changes >= detection.changes.max(1) => {
+                self.flapping = true;
+
+                Some(Flap::Started { changes })
+            }
+            true if changes == 0 => {
+                self.flapping = false;
+
+                Some(Flap::Stopped)
+            }
+            _ => None,
+        }
+    }
+
+    /// Number of times the recent checks changed between up and down
+    fn changes(&self) -> u32 {
+        self.history
+            .iter()
+            .zip(self.history.iter().skip(1))
+            .filter(|(previous, next)| previous != next)
+            .count() as u32
+    }
+
+    pub fn is_flapping(&self) -> bool {
+        self.flapping
+    }
+
+    /// Whether enough checks failed in a row for an incident to be opened
+    pub fn failure_confirmed(&self, monitor: &Monitor) -> bool {
+        !self.flapping && self.failures >= monitor.failures_before_incident.unwrap_or(1).max(1)
+    }
+
+    /// Whether enough checks succeeded in a row for incidents to move towards resolution
+    pub fn recovery_confirmed(&self, monitor: &Monitor) -> bool {
+        !self.flapping && self.successes >= monitor.successes_before_recovery.unwrap_or(1).max(1)
+    }
+}
+
+/// Status of a component, given how each monitor affecting it is failing or `None` for those which are up
+///
+/// The component has a major outage if every monitor is down, a partial
+/// outage if only some are, and degraded performance if none are down but
+/// some are degraded. Returns `None` if every monitor is up.
+pub fn component_status<I>(monitors: I) -> Option<&'static str>
+where
+    I: IntoIterator<Item = Option<Severity>>,
+{
+    let (mut total, mut outages, mut degraded) = (0, 0, 0);
+
+    for severity in monitors {
+        total += 1;
+
+        match severity {
+            Some(Severity::Outage) => outages += 1,
+            Some(Severity::Degraded) => degraded += 1,
+            None => {}
+        }
+    }
+
+    match (outages, degraded) {
+        (0, 0) => None,
+        (0, _) => Some(Severity::Degraded.component_status()),
+        (outages, _) if outages == total => Some(Severity::Outage.component_status()),
+        _ => Some("PARTIALOUTAGE"),
+    }
+}
@@ -1,508 +1,938 @@
-use crate::config::{Config, Monitor, MonitorType};
+use crate::{
+    check::{check_monitor, CheckResult, Severity},
+    config::{
+        Config, Monitor, MonitorType, DEFAULT_CONCURRENT_CHECKS, DEFAULT_CONNECTION_TIMEOUT,
+        DEFAULT_INCIDENT_MONITORING_THRESHOLD,
+    },
+    error::{Result, VelocityError},
+    event::{Event, EventHandler},
+    heartbeat::{self, Heartbeats, DEFAULT_HEARTBEAT_ADDRESS},
+    net,
+    provider::{
+        self, ComponentResponse, ComponentStatus, Incident, IncidentStatusUpdate, MetricPoint,
+        NewIncident, StatusPage, StatusPageProvider,
+    },
+    shutdown::Shutdown,
+    state::{self, Flap, MonitorState},
+};
 use chrono::Local;
-use owo_colors::OwoColorize;
-use serde::{Deserialize, Serialize};
+use futures::{stream::FuturesUnordered, StreamExt};
+use smol::{
+    future::{self, FutureExt},
+    net::TcpListener,
+    Task, Timer,
+};
 use std::{
-    collections::HashMap,
-    thread::sleep,
+    collections::{HashMap, HashSet},
+    sync::Arc,
     time::{Duration, Instant},
 };
 use surf::Client;
 
-const MAX_MS_TIME: u8 = 6;
-
-/// A status page object from the Instatus API
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct StatusPage {
-    /// ID of the status page
-    pub id: String,
-    /// Name of the status page
-    pub name: String,
+/// Statistics about a monitoring session, reported on shutdown
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    /// number of checks performed
+    pub checks: u64,
+    /// number of checks which failed
+    pub failures: u64,
+    pub incidents_opened: u64,
+    pub incidents_resolved: u64,
+    /// how long velocity was monitoring for
+    pub uptime: Duration,
 }
 
-/// A metric object from the Instatus API
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Metric {
-    /// ID of the metric
-    pub id: String,
-    /// Name of the metric
-    pub name: String,
+/// Exponential backoff applied after consecutive recoverable errors
+#[derive(Debug, Default)]
+struct Backoff {
+    failures: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct LatencyPost {
-    timestamp: u64,
-    value: u128,
-}
+impl Backoff {
+    const MAX_DELAY: Duration = Duration::from_secs(60);
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct ComponentStatus {
-    id: String,
-    status: String,
-}
+    /// Delay before the next attempt, doubling with every consecutive failure
+    fn next_delay(&mut self) -> Duration {
+        let delay = Duration::from_secs(1 << self.failures.min(6)).min(Self::MAX_DELAY);
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct IncidentPost {
-    name: String,
-    message: String,
-    components: Vec<String>,
-    started: String,
-    status: String,
-    notify: bool,
-    statuses: Vec<ComponentStatus>,
-}
+        self.failures += 1;
+
+        delay
+    }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct IncidentUpdate {
-    message: String,
-    components: Vec<String>,
-    started: String,
-    status: String,
-    notify: bool,
-    statuses: Vec<ComponentStatus>,
+    fn reset(&mut self) {
+        self.failures = 0;
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ComponentResponse {
-    id: String,
-    name: String,
+/// Reasons for the monitor loop to wake up
+enum Wake {
+    /// a check has completed
+    Checked(Box<CheckResult>),
+    /// a monitor has become due
+    Due,
+    /// a shutdown has been requested
+    Shutdown,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Incident {
-    id: String,
-    started: String,
-    status: String,
+/// A status page connected to, ready to be monitored
+pub struct Velocity {
+    config: Config,
+    /// client used to check monitors
+    client: Client,
+    provider: Arc<dyn StatusPageProvider>,
+    page: StatusPage,
     components: Vec<ComponentResponse>,
+    /// IDs of the metrics latency monitors report to, by monitor name
+    metrics: HashMap<String, String>,
+    handler: Option<EventHandler>,
 }
 
-pub async fn post_incident_status(
-    client: Client,
-    page_id: String,
-    incident: Incident,
-    status: String,
-) {
-    client
-        .post(
-            format!(
-                "https://api.instatus.com/v1/{}/incidents/{}/incident-updates",
-                page_id, incident.id
+impl Velocity {
+    /// Connect to the status page provider selected in `config` and run pre-flight setup
+    pub async fn connect(config: Config) -> Result<Self> {
+        Self::connect_with_handler(config, None).await
+    }
+
+    /// Connect like [`connect`](Self::connect), passing the events emitted
+    /// during pre-flight setup to `handler`
+    ///
+    /// The handler is kept to receive every event emitted while monitoring,
+    /// as if registered with [`on_event`](Self::on_event).
+    pub async fn connect_with_events<F>(config: Config, handler: F) -> Result<Self>
+    where
+        F: Fn(&Event) + Send + Sync + 'static,
+    {
+        Self::connect_with_handler(config, Some(Arc::new(handler))).await
+    }
+
+    async fn connect_with_handler(config: Config, handler: Option<EventHandler>) -> Result<Self> {
+        // checks are timed out per monitor, so only requests to the status page API get a client-wide timeout
+        let client = net::build_client(None)?;
+
+        let provider = provider::from_config(
+            &config,
+            net::build_client(Some(Duration::from_secs(
+                config
+                    .max_connection_timeout
+                    .unwrap_or(DEFAULT_CONNECTION_TIMEOUT),
+            )))?,
+        );
+
+        if let Some(handler) = &handler {
+            handler(&Event::Connecting {
+                host: provider.host(),
+            });
+        }
+
+        let mut velocity = Self::with_provider(config, client, provider).await?;
+        velocity.handler = handler;
+
+        Ok(velocity)
+    }
+
+    /// Run pre-flight setup against `provider`, checking monitors with `client`
+    pub async fn with_provider(
+        config: Config,
+        client: Client,
+        provider: Arc<dyn StatusPageProvider>,
+    ) -> Result<Self> {
+        let (metrics, components, page) = net::pre_flight_setup(&config, provider.as_ref()).await?;
+
+        Ok(Self {
+            config,
+            client,
+            provider,
+            page,
+            components,
+            metrics,
+            handler: None,
+        })
+    }
+
+    /// Register a callback receiving every event emitted while monitoring
+    pub fn on_event<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Event) + Send + Sync + 'static,
+    {
+        self.handler = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The status page being reported to
+    pub fn page(&self) -> &StatusPage {
+        &self.page
+    }
+
+    pub fn provider(&self) -> &Arc<dyn StatusPageProvider> {
+        &self.provider
+    }
+
+    fn emit(&self, event: Event) {
+        if let Some(handler) = &self.handler {
+            handler(&event);
+        }
+    }
+
+    /// Report a recoverable error, passing fatal errors on so they stop the monitor loop
+    fn recover(&self, err: VelocityError) -> Result<()> {
+        if err.is_fatal() {
+            return Err(err);
+        }
+
+        self.emit(Event::Error {
+            message: err.to_string(),
+        });
+
+        Ok(())
+    }
+
+    async fn post_incident_status(&self, incident: &Incident, status: &str) -> Result<()> {
+        self.provider
+            .update_incident(
+                &self.page.id,
+                &incident.id,
+                IncidentStatusUpdate {
+                    message: "A fix has been implemented. We are monitoring the service closely."
+                        .to_string(),
+                    components: incident
+                        .components
+                        .iter()
+                        .map(|v| v.id.clone())
+                        .collect::<Vec<String>>(),
+                    started: incident.started.clone(),
+                    status: status.to_string(),
+                    notify: true,
+                    statuses: incident
+                        .components
+                        .iter()
+                        .map(|v| ComponentStatus {
+                            id: v.id.clone(),
+                            status: "OPERATIONAL".to_string(),
+                        })
+                        .collect::<Vec<ComponentStatus>>(),
+                },
             )
-            .as_str(),
-        )
-        .body_json(&IncidentUpdate {
-            message: "A fix has been implemented. We are monitoring the service closely."
-                .to_string(),
-            components: incident
-                .components
+            .await
+    }
+
+    /// Push the latency of a check to the metric of its monitor, if it reports to one
+    async fn report_latency(&self, name: &str, monitor: &Monitor, latency: u128) -> Result<()> {
+        if monitor.metric_name(name).is_none() {
+            return Ok(());
+        }
+
+        let start = Instant::now();
+
+        let metric = self
+            .metrics
+            .get(name)
+            .ok_or_else(|| VelocityError::MissingMetric(name.to_string()))?;
+
+        self.provider
+            .push_metric_point(
+                &self.page.id,
+                metric,
+                MetricPoint {
+                    timestamp: Local::now().timestamp_millis() as u64,
+                    value: latency,
+                },
+            )
+            .await?;
+
+        self.emit(Event::LatencyReported {
+            name: name.to_string(),
+            latency,
+            elapsed: start.elapsed(),
+        });
+
+        Ok(())
+    }
+
+    async fn set_incident_status(&self, incident: &Incident, status: &str) -> Result<()> {
+        match status {
+            "RESOLVED" | "MONITORING" => self.post_incident_status(incident, status).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Start the listener receiving pings to heartbeat monitors
+    async fn listen_for_heartbeats(&self, heartbeats: Heartbeats) -> Result<Task<()>> {
+        let address = self
+            .config
+            .heartbeat_address
+            .as_deref()
+            .unwrap_or(DEFAULT_HEARTBEAT_ADDRESS);
+
+        let error = |source| VelocityError::Heartbeat {
+            address: address.to_string(),
+            source,
+        };
+
+        let listener = TcpListener::bind(address).await.map_err(error)?;
+
+        self.emit(Event::HeartbeatListening {
+            address: listener.local_addr().map_err(error)?,
+        });
+
+        Ok(smol::spawn(heartbeat::serve(
+            listener,
+            heartbeats,
+            self.handler.clone(),
+        )))
+    }
+
+    /// Components affected by the monitor named `name`
+    fn components_of(&self, name: &str, monitor: &Monitor) -> Vec<&ComponentResponse> {
+        self.components
+            .iter()
+            .filter(|component| monitor.affects(name, component))
+            .collect()
+    }
+
+    /// Status `component` should have given the monitors which are failing,
+    /// `None` if every monitor affecting it is up
+    fn status_of(
+        &self,
+        component: &ComponentResponse,
+        failing: &HashMap<String, Severity>,
+    ) -> Option<&'static str> {
+        state::component_status(
+            self.config
+                .monitors
                 .iter()
-                .map(|v| v.id.clone())
-                .collect::<Vec<String>>(),
-            started: incident.started,
-            status,
-            notify: true,
-            statuses: incident
+                .filter(|(name, monitor)| {
+                    monitor.opens_incidents() && monitor.affects(name, component)
+                })
+                .map(|(name, _)| failing.get(name).copied()),
+        )
+    }
+
+    /// Report a failed check, opening an incident for monitors which open incidents
+    ///
+    /// `failing` holds the severity of every monitor whose failure is
+    /// confirmed, and `statuses` the status velocity last set for each
+    /// component, which is updated. `opened` holds the IDs of the components
+    /// of incidents opened since `active_incidents` was fetched, and is
+    /// updated too. Returns whether a new incident was opened, failing only
+    /// if the incident couldn't be opened because of a fatal error
+    async fn report_incident_failure(
+        &self,
+        result: &CheckResult,
+        active_incidents: &[Incident],
+        opened: &mut HashSet<String>,
+        failing: &HashMap<String, Severity>,
+        statuses: &mut HashMap<String, &'static str>,
+    ) -> Result<bool> {
+        let name = &result.name;
+
+        if !result.monitor.opens_incidents() {
+            return Ok(false);
+        }
+
+        let start = Instant::now();
+
+        let impacted = self.components_of(name, &result.monitor);
+
+        // check if the incident needs to be created
+        // if there's already an incident for one of the components, we can skip it
+        let create_report = !active_incidents.iter().any(|incident| {
+            incident
                 .components
                 .iter()
-                .map(|v| ComponentStatus {
-                    id: v.id.clone(),
-                    status: "OPERATIONAL".to_string(),
-                })
-                .collect::<Vec<ComponentStatus>>(),
-        })
-        .unwrap_or_else(|err| {
-            eprintln!(
-                "\n❌ Failed to generate POST request body: {}",
-                err.bright_yellow(),
-            );
+                .any(|component| impacted.iter().any(|other| other.id == component.id))
+        }) && !impacted
+            .iter()
+            .any(|component| opened.contains(&component.id));
 
-            std::process::exit(1);
-        })
-        .await
-        .unwrap_or_else(|err| {
-            eprintln!(
-                "\n❌ Failed to update incident status: {}",
-                err.bright_yellow(),
-            );
-
-            std::process::exit(1);
-        });
-}
+        if !create_report {
+            return Ok(false);
+        }
 
-pub async fn set_incident_status(
-    client: Client,
-    page_id: String,
-    incident: Incident,
-    status: String,
-) {
-    match status.as_str() {
-        "RESOLVED" | "MONITORING" => {
-            post_incident_status(client, page_id, incident, status).await;
+        let impacted_components: Vec<String> = impacted
+            .iter()
+            .map(|component| component.id.to_owned())
+            .collect();
+
+        // components shared with monitors which are up only have a partial outage
+        let impacted_statuses: Vec<(&ComponentResponse, &'static str)> = impacted
+            .iter()
+            .map(|component| {
+                let status = self
+                    .status_of(component, failing)
+                    .unwrap_or_else(|| result.severity.component_status());
+
+                (*component, status)
+            })
+            .collect();
+
+        let impacted_components_statuses = impacted_statuses
+            .iter()
+            .map(|(component, status)| ComponentStatus {
+                id: component.id.clone(),
+                status: status.to_string(),
+            })
+            .collect();
+
+        let res = self
+            .provider
+            .create_incident(
+                &self.page.id,
+                NewIncident {
+                    name: format!("{} Issues", name),
+                    message: match (result.severity, &result.step) {
+                        (Severity::Degraded, _) if result.is_slow() => format!(
+                            "We've identified increased response times of the {}. Engineers have been notified.",
+                            name
+                        ),
+                        (Severity::Degraded, _) => format!(
+                            "We've identified degraded performance of the {}. Engineers have been notified.",
+                            name
+                        ),
+                        (Severity::Outage, Some(step)) => format!(
+                            "We've identified issues with the {}, {} failed. Engineers have been notified.",
+                            name, step
+                        ),
+                        (Severity::Outage, None) => format!(
+                            "We've identified issues with the {}. Engineers have been notified.",
+                            name
+                        ),
+                    },
+                    components: impacted_components,
+                    started: Local::now(),
+                    status: String::from("IDENTIFIED"),
+                    notify: true,
+                    statuses: impacted_components_statuses,
+                },
+            )
+            .await;
+
+        match res {
+            Ok(()) => {
+                for (component, status) in impacted_statuses {
+                    opened.insert(component.id.clone());
+                    statuses.insert(component.id.clone(), status);
+                }
+
+                self.emit(Event::IncidentOpened {
+                    name: name.clone(),
+                    elapsed: start.elapsed(),
+                });
+
+                Ok(true)
+            }
+            Err(err) => {
+                self.emit(Event::IncidentFailed {
+                    name: name.clone(),
+                    elapsed: start.elapsed(),
+                    error: err.to_string(),
+                });
+
+                // incidents keep failing to open until the configuration is fixed
+                if err.is_fatal() {
+                    return Err(err);
+                }
+
+                Ok(false)
+            }
         }
-        &_ => {}
     }
-}
 
-#[allow(clippy::too_many_arguments)]
-pub async fn report_incident_failure(
-    name: String,
-    start: Instant,
-    monitor: &Monitor,
-    client: Client,
-    active_incidents: Vec<Incident>,
-    components: Vec<ComponentResponse>,
-    page: StatusPage,
-    config: Config,
-) {
-    // latency for the request
-    let latency = start.elapsed().as_millis();
-    // current time
-    let time = Local::now();
-
-    // calculate spacing
-    let spacing = " ".repeat(MAX_MS_TIME as usize - latency.to_string().len() as usize);
-
-    match monitor.type_ {
-        MonitorType::Uptime => {
-            println!(
-                "{}  {}{}❌  {} is down",
-                time.format("%H:%M:%S").bright_yellow(),
-                format!("{} ms", latency).bright_black(),
-                spacing,
-                name.bright_red()
-            );
-
-            let start = Instant::now();
-
-            // check if the incident needs to be created
-            // if there's already an incident with the same name, we can skip it
-            let mut create_report = true;
-
-            for incident in active_incidents.iter() {
-                if incident
+    /// Escalate or de-escalate the components of open incidents affected by
+    /// the monitor named `name`, as the share of failing monitors changes
+    ///
+    /// Only statuses velocity set itself are adjusted, so incidents opened by
+    /// hand are left alone. Incidents whose components are all up again are
+    /// left to the recovery of the monitor.
+    async fn update_component_statuses(
+        &self,
+        name: &str,
+        monitor: &Monitor,
+        active_incidents: &[Incident],
+        failing: &HashMap<String, Severity>,
+        statuses: &mut HashMap<String, &'static str>,
+    ) -> Result<()> {
+        let affected = self.components_of(name, monitor);
+
+        for incident in active_incidents {
+            if incident.status != "IDENTIFIED"
+                || !incident
                     .components
                     .iter()
-                    .map(|v| v.name.clone())
-                    .any(|x| x == *name)
-                {
-                    create_report = false;
-                }
+                    .any(|component| affected.iter().any(|other| other.id == component.id))
+            {
+                continue;
             }
 
-            if create_report {
-                let mut impacted_components = vec![];
+            let desired: Vec<(&ComponentResponse, Option<&'static str>)> = incident
+                .components
+                .iter()
+                .map(|component| (component, self.status_of(component, failing)))
+                .collect();
+
+            let changed = desired.iter().any(|(component, status)| {
+                matches!(
+                    statuses.get(&component.id),
+                    Some(current) if *current != status.unwrap_or("OPERATIONAL")
+                )
+            });
+
+            if !changed || desired.iter().all(|(_, status)| status.is_none()) {
+                continue;
+            }
 
-                for component in &components {
-                    if component.name == *name {
-                        impacted_components.push(component.id.to_owned());
-                    }
+            let desired: Vec<(&ComponentResponse, &'static str)> = desired
+                .into_iter()
+                .map(|(component, status)| (component, status.unwrap_or("OPERATIONAL")))
+                .collect();
+
+            self.provider
+                .update_incident(
+                    &self.page.id,
+                    &incident.id,
+                    IncidentStatusUpdate {
+                        message: "The impact of the issue has changed, engineers are still working on it."
+                            .to_string(),
+                        components: desired
+                            .iter()
+                            .map(|(component, _)| component.id.clone())
+                            .collect(),
+                        started: incident.started.clone(),
+                        status: "IDENTIFIED".to_string(),
+                        notify: true,
+                        statuses: desired
+                            .iter()
+                            .map(|(component, status)| ComponentStatus {
+                                id: component.id.clone(),
+                                status: status.to_string(),
+                            })
+                            .collect(),
+                    },
+                )
+                .await?;
+
+            for (component, status) in &desired {
+                statuses.insert(component.id.clone(), status);
+            }
+
+            self.emit(Event::IncidentUpdated {
+                name: name.to_string(),
+                incident_id: incident.id.clone(),
+                statuses: desired
+                    .iter()
+                    .map(|(component, status)| (component.name.clone(), status.to_string()))
+                    .collect(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check every monitor at its frequency until `shutdown` is triggered
+    ///
+    /// Checks still in flight when a shutdown is requested are awaited and
+    /// reported before returning. Recoverable errors are emitted as events,
+    /// fatal ones stop monitoring and are returned.
+    pub async fn run(&self, shutdown: Shutdown) -> Result<Summary> {
+        let config = &self.config;
+
+        self.emit(Event::Started);
+
+        let heartbeats = Heartbeats::new(config);
+
+        // accepts pings until monitoring stops and the task is dropped
+        let _listener = if heartbeats.is_empty() {
+            None
+        } else {
+            Some(self.listen_for_heartbeats(heartbeats.clone()).await?)
+        };
+
+        let mut active_incidents: Vec<Incident> = vec![];
+
+        // when the incidents were last fetched, reset once velocity opens or moves one along
+        let mut incidents_fetched: Option<Instant> = None;
+
+        // incidents are fetched again after a cycle, to notice changes made on the status page
+        let incident_list_lifetime = Duration::from_secs(config.frequency);
+
+        // IDs of the components of incidents opened since the incidents were last fetched,
+        // so monitors sharing a component don't open one incident each
+        let mut opened_components: HashSet<String> = HashSet::new();
+
+        let mut monitoring_elapsed: HashMap<String, u64> = HashMap::new();
+
+        // outcome of recent checks, confirming failures and recoveries, by monitor name
+        let mut states: HashMap<String, MonitorState> = HashMap::new();
+
+        // severity of the monitors whose failure is confirmed, by monitor name
+        let mut failing: HashMap<String, Severity> = HashMap::new();
+
+        // status velocity last set for each component, by component ID
+        let mut component_statuses: HashMap<String, &'static str> = HashMap::new();
+
+        // hash of the body at the last check of monitors detecting content changes
+        let mut content_hashes: HashMap<String, u64> = HashMap::new();
+
+        // time each monitor is next due to be checked, offset by its initial jitter
+        let mut next_check: HashMap<&String, Instant> = config
+            .monitors
+            .iter()
+            .map(|(name, monitor)| {
+                let jitter = fastrand::u64(0..=monitor.jitter.unwrap_or(0).saturating_mul(1000));
+
+                (name, Instant::now() + Duration::from_millis(jitter))
+            })
+            .collect();
+
+        let max_concurrent_checks = config
+            .max_concurrent_checks
+            .unwrap_or(DEFAULT_CONCURRENT_CHECKS)
+            .max(1);
+
+        // checks which have been started but whose results haven't been processed yet
+        let mut in_flight = FuturesUnordered::new();
+        let mut running: HashSet<String> = HashSet::new();
+
+        let started = Instant::now();
+        let mut summary = Summary::default();
+
+        let mut backoff = Backoff::default();
+
+        loop {
+            if shutdown.is_triggered() {
+                if in_flight.is_empty() {
+                    break;
                 }
+            } else {
+                let now = Instant::now();
 
-                let mut impacted_components_statuses = vec![];
+                let mut due = vec![];
 
-                for component in &impacted_components {
-                    impacted_components_statuses.push(ComponentStatus {
-                        id: component.clone(),
-                        status: "MAJOROUTAGE".to_string(),
-                    });
+                for (name, next) in next_check.iter_mut() {
+                    if *next <= now
+                        && !running.contains(*name)
+                        && running.len() + due.len() < max_concurrent_checks
+                    {
+                        let monitor = &config.monitors[*name];
+
+                        *next = now
+                            + Duration::from_secs(monitor.frequency.unwrap_or(config.frequency));
+
+                        due.push((*name, monitor));
+                    }
                 }
 
-                let res = client
-                    .post(format!("https://api.instatus.com/v1/{}/incidents", page.id))
-                    .header("Authorization", format!("Bearer {}", config.api_key))
-                    .body_json(&IncidentPost {
-                        name: format!("{} Issues", name),
-                        message: format!(
-                            "We've identified issues with the {}. Engineers have been notified.",
-                            name
-                        ),
-                        components: impacted_components,
-                        started: time.format("%Y-%m-%d %H:%M:%S.%3f").to_string(),
-                        status: String::from("IDENTIFIED"),
-                        notify: true,
-                        statuses: impacted_components_statuses,
-                    })
-                    .unwrap_or_else(|err| {
-                        eprintln!(
-                            "❌ Failed to generate POST request body: {}",
-                            err.bright_yellow(),
-                        );
-
-                        std::process::exit(1);
-                    })
-                    .send()
-                    .await;
-
-                match res {
-                    Ok(res) => {
-                        if res.status().is_success() {
-                            println!(
-                                "{}  {}{}🎫  Successfully created incident for {} ",
-                                time.format("%H:%M:%S").bright_yellow(),
-                                format!("{} ms", start.elapsed().as_millis()).bright_black(),
-                                " ".repeat(
-                                    MAX_MS_TIME as usize
-                                        - start.elapsed().as_millis().to_string().len() as usize
-                                ),
-                                name.bright_green()
-                            );
-                        } else {
-                            println!(
-                                "{}  {}{}❌  Failed to create incident for {} ",
-                                time.format("%H:%M:%S").bright_yellow(),
-                                format!("{} ms", start.elapsed().as_millis()).bright_black(),
-                                " ".repeat(
-                                    MAX_MS_TIME as usize
-                                        - start.elapsed().as_millis().to_string().len() as usize
-                                ),
-                                name.bright_red()
-                            );
+                if !due.is_empty() {
+                    // get a list of incidents, unless the last one is still current
+                    let current = incidents_fetched
+                        .is_some_and(|fetched| fetched.elapsed() < incident_list_lifetime);
+
+                    let incidents = match current {
+                        true => Ok(None),
+                        false => self.provider.list_incidents(&self.page.id).await.map(Some),
+                    };
+
+                    match incidents {
+                        Ok(incidents) => {
+                            if let Some(incidents) = incidents {
+                                backoff.reset();
+
+                                // an incident is still active if its status is one that is not resolved
+                                active_incidents = incidents
+                                    .into_iter()
+                                    .filter(|incident| {
+                                        incident.status == "IDENTIFIED"
+                                            || incident.status == "MONITORING"
+                                    })
+                                    .collect();
+
+                                opened_components.clear();
+
+                                incidents_fetched = Some(Instant::now());
+                            }
+
+                            // start checking every due monitor, results are processed as they arrive
+                            for (name, monitor) in due {
+                                running.insert(name.clone());
+
+                                let (client, name, monitor) =
+                                    (self.client.clone(), name.clone(), monitor.clone());
+                                let heartbeats = heartbeats.clone();
+
+                                in_flight.push(smol::spawn(async move {
+                                    match monitor.type_ {
+                                        MonitorType::Heartbeat => heartbeats.check(name, monitor),
+                                        _ => check_monitor(client, name, monitor).await,
+                                    }
+                                }));
+                            }
+                        }
+                        Err(err) => {
+                            self.recover(err)?;
+
+                            // without knowing which incidents are open checks could open duplicates,
+                            // so hold them back until the incidents can be fetched again
+                            let delay = backoff.next_delay();
+
+                            self.emit(Event::Retrying { delay });
+
+                            for (name, _) in due {
+                                next_check.insert(name, now + delay);
+                            }
                         }
                     }
-                    Err(_) => {
-                        println!(
-                            "{}  {}{}❌  Failed to create incident for {} ",
-                            time.format("%H:%M:%S").bright_yellow(),
-                            format!("{} ms", start.elapsed().as_millis()).bright_black(),
-                            " ".repeat(
-                                MAX_MS_TIME as usize
-                                    - start.elapsed().as_millis().to_string().len() as usize
-                            ),
-                            name.bright_red()
-                        );
+                }
+            }
+
+            // the next monitor which can be started, if a slot is free
+            let next_due = next_check
+                .iter()
+                .filter(|(name, _)| !running.contains(**name))
+                .map(|(_, next)| *next)
+                .min()
+                .filter(|_| running.len() < max_concurrent_checks);
+
+            // wait for a check to complete, the next monitor to become due or a shutdown request
+            let wake = async {
+                match in_flight.next().await {
+                    Some(result) => Wake::Checked(Box::new(result)),
+                    None => future::pending().await,
+                }
+            }
+            .or(async {
+                match next_due {
+                    Some(next) if !shutdown.is_triggered() => {
+                        Timer::at(next).await;
+
+                        Wake::Due
                     }
+                    _ => future::pending().await,
+                }
+            })
+            .or(async {
+                if shutdown.is_triggered() {
+                    future::pending().await
+                } else {
+                    shutdown.wait().await;
+
+                    Wake::Shutdown
                 }
+            })
+            .await;
+
+            if let Wake::Shutdown = wake {
+                self.emit(Event::ShuttingDown {
+                    in_flight: in_flight.len(),
+                });
             }
-        }
-        MonitorType::Latency => {
-            println!(
-                "{}  {}{}⚠️   Unable to measure latency for {}",
-                time.format("%H:%M:%S").bright_yellow(),
-                format!("{} ms", latency).bright_black(),
-                spacing,
-                name.bright_yellow()
-            );
-        }
-    }
-}
 
-pub async fn monitor(
-    page: StatusPage,
-    components: Vec<ComponentResponse>,
-    metrics: HashMap<String, String>,
-    client: Client,
-    config: Config,
-) {
-    println!("🔍 Monitoring requests...");
+            if let Wake::Checked(mut result) = wake {
+                running.remove(&result.name);
 
-    let mut active_incidents: Vec<Incident> = vec![];
+                if let Some(hash) = result.content_hash {
+                    let previous = content_hashes.insert(result.name.clone(), hash);
 
-    let mut active_monitoring_incidents: Vec<Incident> = vec![];
+                    if let (Some(content), Some(previous)) = (&result.monitor.content, previous) {
+                        if previous != hash && result.is_success() {
+                            let failure =
+                                content.failure("content changed since the last check".to_string());
 
-    let mut monitoring_elapsed: HashMap<String, u64> = HashMap::new();
+                            result.failure = Some(failure.reason);
+                            result.severity = failure.severity;
+                        }
+                    }
+                }
 
-    loop {
-        active_incidents.clear();
+                let state = states.entry(result.name.clone()).or_default();
 
-        // get a list of incidents
-        let incidents = client
-            .get(format!("https://api.instatus.com/v1/{}/incidents", page.id))
-            .header("Authorization", format!("Bearer {}", config.api_key))
-            .recv_json::<Vec<Incident>>()
-            .await
-            .unwrap_or_else(|err| {
-                eprintln!(
-                    "\n❌ Failed to get list of incidents: {}",
-                    err.bright_yellow(),
-                );
+                // slow checks still measured the latency, which is reported along with the failure
+                let slow = match state.record_latency(&result) {
+                    Some(failure) => {
+                        result.failure = Some(failure.reason);
+                        result.severity = failure.severity;
 
-                std::process::exit(1);
-            });
+                        true
+                    }
+                    None => false,
+                };
+
+                summary.checks += 1;
+
+                self.emit(Event::Checked(result.clone()));
+
+                match (
+                    state.record(&result.monitor, result.is_success()),
+                    result.monitor.flap_detection,
+                ) {
+                    (Some(Flap::Started { changes }), Some(detection)) => {
+                        self.emit(Event::FlappingStarted {
+                            name: result.name.clone(),
+                            changes,
+                            checks: detection.checks,
+                        })
+                    }
+                    (Some(Flap::Stopped), _) => self.emit(Event::FlappingStopped {
+                        name: result.name.clone(),
+                    }),
+                    _ => {}
+                }
 
-        // if the incident is still valid / active append it to the array of active incidents
-        for incident in incidents {
-            // an incident is still valid if the status is still one that is not resolved
-            if incident.status == "IDENTIFIED" || incident.status == "MONITORING" {
-                // if the status is still active
-                active_incidents.push(incident.clone());
+                // failures and recoveries only count once confirmed, and not while flapping
+                if result.is_success() {
+                    if state.recovery_confirmed(&result.monitor) {
+                        failing.remove(&result.name);
+                    }
+                } else if state.failure_confirmed(&result.monitor) {
+                    failing.insert(result.name.clone(), result.severity);
+                }
 
-                if incident.status == "MONITORING" {
-                    active_monitoring_incidents.push(incident.clone());
+                if result.monitor.opens_incidents() {
+                    if let Err(err) = self
+                        .update_component_statuses(
+                            &result.name,
+                            &result.monitor,
+                            &active_incidents,
+                            &failing,
+                            &mut component_statuses,
+                        )
+                        .await
+                    {
+                        self.recover(err)?;
+                    }
                 }
-            }
-        }
 
-        // iterate through all monitor names and endpoints
-        for (name, monitor) in config.monitors.iter() {
-            let start = Instant::now();
-
-            let res = client
-                .get(&monitor.url)
-                .header("Cache-Control", "no-cache, no-store, must-revalidate")
-                .header("Pragma", "no-cache")
-                .header("Expires", "0")
-                .send()
-                .await;
-
-            match res {
-                Ok(response) => {
-                    if response.status().is_success() {
-                        // latency for the request
-                        let latency = start.elapsed().as_millis();
-                        // current time
-                        let time = Local::now();
-
-                        // calculate spacing
-                        let spacing =
-                            " ".repeat(MAX_MS_TIME as usize - latency.to_string().len() as usize);
-
-                        if let MonitorType::Uptime = monitor.type_ {
-                            println!(
-                                "{}  {}{}✅  {} is up",
-                                time.format("%H:%M:%S").bright_yellow(),
-                                format!("{} ms", latency).bright_black(),
-                                spacing,
-                                name.bright_green()
-                            );
+                if !result.is_success() {
+                    summary.failures += 1;
 
-                            for incident in active_incidents.iter() {
-                                if incident.status == "MONITORING" {
-                                    if monitoring_elapsed[&incident.id] == 0 {
-                                        let start = Instant::now();
-                                        set_incident_status(
-                                            client.clone(),
-                                            page.id.clone(),
-                                            incident.clone(),
-                                            "RESOLVED".to_string(),
-                                        )
-                                        .await;
-
-                                        println!(
-                                            "{}  {}{}✅  {} marked as resolved",
-                                            time.format("%H:%M:%S").bright_yellow(),
-                                            format!("{} ms", start.elapsed().as_secs_f32())
-                                                .bright_black(),
-                                            spacing,
-                                            name.bright_green()
-                                        );
-                                    } else {
-                                        *monitoring_elapsed.get_mut(&incident.id).unwrap() -= 1;
-                                    }
-                                } else {
-                                    // if the status is not monitoring, then change this incident to MONITORING
-                                    if incident.status.as_str() == "IDENTIFIED" {
-                                        monitoring_elapsed.insert(
-                                            incident.id.clone(),
-                                            config.incident_monitoring_threshold.unwrap(),
-                                        );
-
-                                        set_incident_status(
-                                            client.clone(),
-                                            page.id.clone(),
-                                            incident.clone(),
-                                            "MONITORING".to_string(),
-                                        )
-                                        .await;
-                                    }
+                    if slow {
+                        if let Err(err) = self
+                            .report_latency(&result.name, &result.monitor, result.latency)
+                            .await
+                        {
+                            self.recover(err)?;
+                        }
+                    }
+
+                    if state.failure_confirmed(&result.monitor)
+                        && self
+                            .report_incident_failure(
+                                &result,
+                                &active_incidents,
+                                &mut opened_components,
+                                &failing,
+                                &mut component_statuses,
+                            )
+                            .await?
+                    {
+                        summary.incidents_opened += 1;
+
+                        incidents_fetched = None;
+                    }
+
+                    continue;
+                }
+
+                let CheckResult {
+                    name,
+                    monitor,
+                    latency,
+                    ..
+                } = *result;
+
+                if monitor.opens_incidents() && state.recovery_confirmed(&monitor) {
+                    let affected = self.components_of(&name, &monitor);
+
+                    for incident in active_incidents.iter() {
+                        // incidents of other monitors recover with their own checks
+                        if !incident
+                            .components
+                            .iter()
+                            .any(|component| affected.iter().any(|other| other.id == component.id))
+                        {
+                            continue;
+                        }
+
+                        // components other monitors are still failing for keep their incident open
+                        if incident
+                            .components
+                            .iter()
+                            .any(|component| self.status_of(component, &failing).is_some())
+                        {
+                            continue;
+                        }
+
+                        if incident.status == "MONITORING" {
+                            // once it's passed its monitoring time, move it to resolved
+                            if monitoring_elapsed
+                                .get(&incident.id)
+                                .copied()
+                                .unwrap_or_default()
+                                == 0
+                            {
+                                let start = Instant::now();
+
+                                if let Err(err) =
+                                    self.set_incident_status(incident, "RESOLVED").await
+                                {
+                                    self.recover(err)?;
+
+                                    continue;
                                 }
-                                // otherwise, if it is MONITORING and it's passed it's monitoring time, move it to resolved
-                                // while marking things as resolved, ensure that all the components related to it are marked as resolved as well
-                            }
-                        } else {
-                            let start = Instant::now();
-
-                            client
-                                .post(format!(
-                                    "https://api.instatus.com/v1/{}/metrics/{}",
-                                    page.id,
-                                    metrics.get(name).unwrap_or_else(|| {
-                                        eprintln!(
-                                            "\n❌ Could not detect any metrics corresponding to {}\n\nTo learn how to setup a metric, see https://hydralite.io/velocity/docs/metrics",
-                                            name.bright_cyan(),
-                                        );
-
-                                        std::process::exit(1);
-                                    })
-                                ))
-                                .header("Authorization", format!("Bearer {}", config.api_key))
-                                .body_json(&LatencyPost {
-                                    timestamp: time.timestamp_millis() as u64,
-                                    value: latency,
-                                })
-                                .unwrap_or_else(|err| {
-                                    eprintln!(
-                                        "\n❌ Failed to generate POST request: {}",
-                                        err.bright_yellow(),
-                                    );
-
-                                    std::process::exit(1);
-                                })
-                                .await
-                                .unwrap_or_else(|err| {
-                                    eprintln!(
-                                        "\n❌ Failed to update latency: {}",
-                                        err.bright_yellow(),
-                                    );
-                                    
-                                    std::process::exit(1);
-                                })
-                                .body_string()
-                                .await
-                                .unwrap_or_else(|err| {
-                                    eprintln!(
-                                        "\n❌ Failed to read response body: {}",
-                                        err.bright_yellow(),
-                                    );
-
-                                    std::process::exit(1);
-                                });
 
-                            println!(
-                                "{}  {}{}📡  {} latency updated to {}",
-                                time.format("%H:%M:%S").bright_yellow(),
-                                format!("{} ms", start.elapsed().as_millis()).bright_black(),
-                                " ".repeat(
-                                    MAX_MS_TIME as usize
-                                        - start.elapsed().as_millis().to_string().len() as usize
-                                ),
-                                name.bright_green(),
-                                format!("{} ms", latency).bright_black(),
+                                summary.incidents_resolved += 1;
+
+                                incidents_fetched = None;
+
+                                self.emit(Event::IncidentResolved {
+                                    name: name.clone(),
+                                    incident_id: incident.id.clone(),
+                                    elapsed: start.elapsed(),
+                                });
+                            } else {
+                                *monitoring_elapsed.get_mut(&incident.id).unwrap() -= 1;
+                            }
+                        } else if incident.status == "IDENTIFIED" {
+                            // if the status is not monitoring, then change this incident to MONITORING
+                            monitoring_elapsed.insert(
+                                incident.id.clone(),
+                                config
+                                    .incident_monitoring_threshold
+                                    .unwrap_or(DEFAULT_INCIDENT_MONITORING_THRESHOLD),
                             );
-                        }
-                    } else {
-                        report_incident_failure(
-                            name.to_string(),
-                            start,
-                            monitor,
-                            client.clone(),
-                            active_incidents.clone(),
-                            components.clone(),
-                            page.clone(),
-                            config.clone(),
-                        )
-                        .await;
 
-                        continue;
+                            if let Err(err) = self.set_incident_status(incident, "MONITORING").await
+                            {
+                                self.recover(err)?;
+
+                                continue;
+                            }
+
+                            for component in &incident.components {
+                                component_statuses.remove(&component.id);
+                            }
+
+                            incidents_fetched = None;
+
+                            self.emit(Event::IncidentMonitoring {
+                                name: name.clone(),
+                                incident_id: incident.id.clone(),
+                            });
+                        }
                     }
                 }
-                Err(_err) => {
-                    report_incident_failure(
-                        name.to_string(),
-                        start,
-                        monitor,
-                        client.clone(),
-                        active_incidents.clone(),
-                        components.clone(),
-                        page.clone(),
-                        config.clone(),
-                    )
-                    .await;
 
-                    continue;
+                if let Err(err) = self.report_latency(&name, &monitor, latency).await {
+                    self.recover(err)?;
                 }
             }
         }
 
-        sleep(Duration::from_secs(config.frequency));
+        summary.uptime = started.elapsed();
+
+        Ok(summary)
     }
 }
@@ -0,0 +1,122 @@
+use serde_json::json;
+use velocity::assertion::{resolve, Assertions, Content, StatusRange};
+
+fn assertions(value: serde_json::Value) -> Assertions {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn status_ranges() {
+    let assertions = assertions(json!({ "status": [204, "3xx", "400-404"] }));
+
+    assert_eq!(
+        assertions.status.clone().unwrap(),
+        vec![
+            StatusRange {
+                start: 204,
+                end: 204
+            },
+            StatusRange {
+                start: 300,
+                end: 399
+            },
+            StatusRange {
+                start: 400,
+                end: 404
+            },
+        ]
+    );
+
+    assert!(assertions.verify_status(204).is_ok());
+    assert!(assertions.verify_status(302).is_ok());
+    assert!(assertions.verify_status(404).is_ok());
+    assert_eq!(
+        assertions.verify_status(200).unwrap_err(),
+        "unexpected status 200, expected 204, 3xx, 400-404"
+    );
+
+    assert_eq!(
+        Assertions::default().verify_status(503).unwrap_err(),
+        "unexpected status 503"
+    );
+
+    for invalid in [json!("abc"), json!("500-400"), json!("999xx")] {
+        assert!(serde_json::from_value::<Assertions>(json!({ "status": [invalid] })).is_err());
+    }
+}
+
+#[test]
+fn invalid_regex_is_rejected() {
+    assert!(serde_json::from_value::<Assertions>(json!({ "bodyMatches": "(" })).is_err());
+}
+
+#[test]
+fn json_paths() {
+    let body = json!({ "status": "ok", "checks": [{ "healthy": true }, { "healthy": false }] });
+
+    assert_eq!(resolve(&body, "$.status"), Some(&json!("ok")));
+    assert_eq!(resolve(&body, "status"), Some(&json!("ok")));
+    assert_eq!(resolve(&body, "checks[1].healthy"), Some(&json!(false)));
+    assert_eq!(resolve(&body, "$.checks[2]"), None);
+    assert_eq!(resolve(&body, "$.missing"), None);
+    assert_eq!(resolve(&json!([1, 2]), "$[1]"), Some(&json!(2)));
+}
+
+#[test]
+fn body_assertions() {
+    let body = br#"{"status":"degraded","version":"1.2.3"}"#;
+
+    assert!(assertions(json!({
+        "bodyContains": "version",
+        "bodyMatches": "\\d+\\.\\d+\\.\\d+",
+        "json": { "$.version": "1.2.3" },
+        "maxSize": 100,
+    }))
+    .verify_body(body)
+    .is_ok());
+
+    assert_eq!(
+        assertions(json!({ "bodyContains": "maintenance" }))
+            .verify_body(body)
+            .unwrap_err(),
+        "body does not contain \"maintenance\""
+    );
+    assert_eq!(
+        assertions(json!({ "bodyMatches": "^ok$" }))
+            .verify_body(body)
+            .unwrap_err(),
+        "body does not match /^ok$/"
+    );
+    assert_eq!(
+        assertions(json!({ "json": { "$.status": "ok" } }))
+            .verify_body(body)
+            .unwrap_err(),
+        "expected $.status to be \"ok\", got \"degraded\""
+    );
+    assert_eq!(
+        assertions(json!({ "json": { "$.db": true } }))
+            .verify_body(body)
+            .unwrap_err(),
+        "$.db is missing from the body"
+    );
+    assert_eq!(
+        assertions(json!({ "maxSize": 10 }))
+            .verify_body(body)
+            .unwrap_err(),
+        "body exceeds the maximum size of 10 bytes"
+    );
+}
+
+#[test]
+fn content_hash_ignores_volatile_parts() {
+    let content: Content = serde_json::from_value(json!({ "ignore": "\\d{2}:\\d{2}" })).unwrap();
+
+    assert_eq!(
+        content.hash(b"Updated at 10:42"),
+        content.hash(b"Updated at 11:07")
+    );
+    assert_ne!(
+        content.hash(b"Updated at 10:42"),
+        content.hash(b"Down at 10:42")
+    );
+}
@@ -0,0 +1,932 @@
+//! A mock Instatus API used by the integration tests
+//!
+//! The server keeps every incident, incident update and metric point it
+//! receives in memory so tests can assert on what velocity reported. It also
+//! serves a `/health` endpoint whose status code can be toggled, which is
+//! used as the endpoint being monitored.
+
+#![allow(dead_code)]
+
+use std::{
+    convert::TryFrom,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use http_types::{Method, Request, Response, StatusCode};
+use serde_json::{json, Value};
+use smol::net::TcpListener;
+use velocity::{Config, Event, Result, Shutdown, Summary, Velocity};
+
+pub const API_KEY: &str = "test-api-key";
+pub const PAGE_ID: &str = "page-1";
+pub const PAGE_NAME: &str = "Velocity";
+
+#[derive(Debug, Clone)]
+pub struct MockIncident {
+    pub id: String,
+    pub name: String,
+    pub message: String,
+    pub started: String,
+    pub status: String,
+    pub components: Vec<String>,
+    pub statuses: Vec<(String, String)>,
+    pub updates: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricPoint {
+    pub metric_id: String,
+    pub timestamp: u64,
+    pub value: u64,
+}
+
+/// Status, headers and body served by the custom endpoint
+type CustomResponse = (u16, Vec<(String, String)>, String);
+
+/// A request received by the echo endpoint
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    components: Vec<(String, String)>,
+    metrics: Vec<(String, String)>,
+    incidents: Vec<MockIncident>,
+    points: Vec<MetricPoint>,
+    healthy: bool,
+    slow_in_flight: usize,
+    slow_max_in_flight: usize,
+    incident_list_failures: usize,
+    incident_lists: usize,
+    incident_rejection: Option<u16>,
+    health_failures: usize,
+    custom: Option<CustomResponse>,
+    echoed: Vec<RecordedRequest>,
+}
+
+/// Handle to a running mock Instatus server
+#[derive(Clone)]
+pub struct MockInstatus {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockInstatus {
+    /// Start a mock server on a random local port, in a background thread
+    pub fn start() -> Self {
+        let listener = smol::block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let state = Arc::new(Mutex::new(State {
+            healthy: true,
+            ..Default::default()
+        }));
+
+        let server = Self { addr, state };
+        let handle = server.clone();
+
+        std::thread::spawn(move || {
+            smol::block_on(async {
+                loop {
+                    let (stream, _) = match listener.accept().await {
+                        Ok(conn) => conn,
+                        Err(_) => continue,
+                    };
+
+                    let handle = handle.clone();
+
+                    smol::spawn(async move {
+                        let _ = async_h1::accept(stream, |req| {
+                            let handle = handle.clone();
+                            async move { Ok(handle.handle(req).await) }
+                        })
+                        .await;
+                    })
+                    .detach();
+                }
+            })
+        });
+
+        server
+    }
+
+    /// Base URL of the mock API, to be used as `apiBaseUrl`
+    pub fn api_url(&self) -> String {
+        format!("http://{}/v1", self.addr)
+    }
+
+    /// URL of the monitored health endpoint
+    pub fn health_url(&self) -> String {
+        format!("http://{}/health", self.addr)
+    }
+
+    /// URL of an endpoint that takes `millis` to respond
+    pub fn slow_url(&self, millis: u64) -> String {
+        format!("http://{}/slow/{}", self.addr, millis)
+    }
+
+    /// URL of an endpoint responding as set by [`MockInstatus::set_response`]
+    pub fn custom_url(&self) -> String {
+        format!("http://{}/custom", self.addr)
+    }
+
+    /// Set the status, headers and body served by the custom endpoint
+    pub fn set_response(&self, status: u16, headers: &[(&str, &str)], body: &str) {
+        self.state.lock().unwrap().custom = Some((
+            status,
+            headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body.to_string(),
+        ));
+    }
+
+    /// URL of an endpoint recording every request it receives
+    pub fn echo_url(&self) -> String {
+        format!("http://{}/echo", self.addr)
+    }
+
+    /// Requests received by the echo endpoint
+    pub fn echoed(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().echoed.clone()
+    }
+
+    /// Highest number of requests to the slow endpoint served at the same time
+    pub fn slow_max_in_flight(&self) -> usize {
+        self.state.lock().unwrap().slow_max_in_flight
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn add_component(&self, id: &str, name: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.components.push((id.to_string(), name.to_string()));
+    }
+
+    pub fn add_metric(&self, id: &str, name: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.metrics.push((id.to_string(), name.to_string()));
+    }
+
+    /// Respond to the next `count` requests listing incidents with a 502
+    pub fn fail_incident_lists(&self, count: usize) {
+        self.state.lock().unwrap().incident_list_failures = count;
+    }
+
+    /// Number of requests listing incidents received so far
+    pub fn incident_lists(&self) -> usize {
+        self.state.lock().unwrap().incident_lists
+    }
+
+    /// Respond to every request opening an incident with `status`
+    pub fn reject_incidents(&self, status: u16) {
+        self.state.lock().unwrap().incident_rejection = Some(status);
+    }
+
+    /// Respond to the next `count` health checks with a 503, whether healthy or not
+    pub fn fail_health_checks(&self, count: usize) {
+        self.state.lock().unwrap().health_failures = count;
+    }
+
+    pub fn set_healthy(&self, healthy: bool) {
+        self.state.lock().unwrap().healthy = healthy;
+    }
+
+    pub fn incidents(&self) -> Vec<MockIncident> {
+        self.state.lock().unwrap().incidents.clone()
+    }
+
+    pub fn metric_points(&self) -> Vec<MetricPoint> {
+        self.state.lock().unwrap().points.clone()
+    }
+
+    /// Poll `condition` until it holds, panicking after `timeout`
+    pub fn wait_for<F: Fn(&Self) -> bool>(&self, timeout: Duration, what: &str, condition: F) {
+        let deadline = Instant::now() + timeout;
+
+        while !condition(self) {
+            if Instant::now() > deadline {
+                panic!("timed out waiting for {}", what);
+            }
+
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    async fn handle(&self, mut req: Request) -> Response {
+        let path = req.url().path().to_string();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        if let ["slow", millis] = segments.as_slice() {
+            let millis = millis.parse().unwrap_or_default();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                state.slow_in_flight += 1;
+                state.slow_max_in_flight = state.slow_max_in_flight.max(state.slow_in_flight);
+            }
+
+            smol::Timer::after(Duration::from_millis(millis)).await;
+
+            self.state.lock().unwrap().slow_in_flight -= 1;
+
+            return Response::new(StatusCode::Ok);
+        }
+
+        if segments == ["custom"] {
+            let (status, headers, body) = self
+                .state
+                .lock()
+                .unwrap()
+                .custom
+                .clone()
+                .unwrap_or_default();
+
+            let mut res = Response::new(StatusCode::try_from(status).unwrap_or(StatusCode::Ok));
+
+            for (name, value) in headers {
+                res.insert_header(name.as_str(), value.as_str());
+            }
+
+            res.set_body(body);
+
+            return res;
+        }
+
+        if segments == ["echo"] {
+            let request = RecordedRequest {
+                method: req.method().to_string(),
+                headers: req
+                    .iter()
+                    .map(|(name, values)| (name.to_string(), values.last().to_string()))
+                    .collect(),
+                body: req.body_string().await.unwrap_or_default(),
+            };
+
+            self.state.lock().unwrap().echoed.push(request);
+
+            return Response::new(StatusCode::Ok);
+        }
+
+        if segments == ["health"] {
+            let mut state = self.state.lock().unwrap();
+
+            if state.health_failures > 0 {
+                state.health_failures -= 1;
+
+                return Response::new(StatusCode::ServiceUnavailable);
+            }
+
+            return if state.healthy {
+                Response::new(StatusCode::Ok)
+            } else {
+                Response::new(StatusCode::ServiceUnavailable)
+            };
+        }
+
+        let authorized = req
+            .header("Authorization")
+            .map(|value| value.as_str() == format!("Bearer {}", API_KEY))
+            .unwrap_or(false);
+
+        if !authorized {
+            return Response::new(StatusCode::Unauthorized);
+        }
+
+        let body: Value = if req.method() == Method::Post {
+            match req.body_json().await {
+                Ok(body) => body,
+                Err(_) => return Response::new(StatusCode::BadRequest),
+            }
+        } else {
+            Value::Null
+        };
+
+        let mut state = self.state.lock().unwrap();
+
+        if req.method() == Method::Get && segments == ["v1", PAGE_ID, "incidents"] {
+            state.incident_lists += 1;
+        }
+
+        match (req.method(), segments.as_slice()) {
+            (Method::Get, ["v1", "pages"]) => {
+                json_response(json!([{ "id": PAGE_ID, "name": PAGE_NAME }]))
+            }
+            (Method::Get, ["v1", PAGE_ID, "components"]) => json_response(Value::Array(
+                state
+                    .components
+                    .iter()
+                    .map(|(id, name)| json!({ "id": id, "name": name }))
+                    .collect(),
+            )),
+            (Method::Get, ["v1", PAGE_ID, "metrics"]) => json_response(Value::Array(
+                state
+                    .metrics
+                    .iter()
+                    .map(|(id, name)| json!({ "id": id, "name": name }))
+                    .collect(),
+            )),
+            (Method::Get, ["v1", PAGE_ID, "incidents"]) if state.incident_list_failures > 0 => {
+                state.incident_list_failures -= 1;
+
+                Response::new(StatusCode::BadGateway)
+            }
+            (Method::Get, ["v1", PAGE_ID, "incidents"]) => {
+                let incidents = state
+                    .incidents
+                    .iter()
+                    .map(|incident| {
+                        json!({
+                            "id": incident.id,
+                            "started": incident.started,
+                            "status": incident.status,
+                            "components": incident
+                                .components
+                                .iter()
+                                .map(|id| {
+                                    let name = state
+                                        .components
+                                        .iter()
+                                        .find(|(component, _)| component == id)
+                                        .map(|(_, name)| name.clone())
+                                        .unwrap_or_default();
+
+                                    json!({ "id": id, "name": name })
+                                })
+                                .collect::<Vec<Value>>(),
+                        })
+                    })
+                    .collect();
+
+                json_response(Value::Array(incidents))
+            }
+            (Method::Post, ["v1", PAGE_ID, "incidents"]) if state.incident_rejection.is_some() => {
+                Response::new(state.incident_rejection.unwrap())
+            }
+            (Method::Post, ["v1", PAGE_ID, "incidents"]) => {
+                let id = format!("incident-{}", state.incidents.len() + 1);
+
+                state.incidents.push(MockIncident {
+                    id: id.clone(),
+                    name: string(&body["name"]),
+                    message: string(&body["message"]),
+                    started: string(&body["started"]),
+                    status: string(&body["status"]),
+                    components: strings(&body["components"]),
+                    statuses: statuses(&body["statuses"]),
+                    updates: vec![],
+                });
+
+                json_response(json!({ "id": id }))
+            }
+            (Method::Post, ["v1", PAGE_ID, "incidents", id, "incident-updates"]) => {
+                match state
+                    .incidents
+                    .iter_mut()
+                    .find(|incident| incident.id == *id)
+                {
+                    Some(incident) => {
+                        let status = string(&body["status"]);
+
+                        incident.status = status.clone();
+                        incident.statuses = statuses(&body["statuses"]);
+                        incident.updates.push(status);
+
+                        json_response(json!({ "id": id }))
+                    }
+                    None => Response::new(StatusCode::NotFound),
+                }
+            }
+            (Method::Post, ["v1", PAGE_ID, "metrics", id]) => {
+                if !state.metrics.iter().any(|(metric, _)| metric == id) {
+                    return Response::new(StatusCode::NotFound);
+                }
+
+                let point = MetricPoint {
+                    metric_id: id.to_string(),
+                    timestamp: body["timestamp"].as_u64().unwrap_or_default(),
+                    value: body["value"].as_u64().unwrap_or_default(),
+                };
+
+                state.points.push(point);
+
+                json_response(json!({}))
+            }
+            _ => Response::new(StatusCode::NotFound),
+        }
+    }
+}
+
+fn json_response(body: Value) -> Response {
+    let mut res = Response::new(StatusCode::Ok);
+    res.set_body(body);
+    res
+}
+
+fn string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|values| values.iter().map(string).collect())
+        .unwrap_or_default()
+}
+
+fn statuses(value: &Value) -> Vec<(String, String)> {
+    value
+        .as_array()
+        .map(|values| {
+            values
+                .iter()
+                .map(|status| (string(&status["id"]), string(&status["status"])))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Build a configuration pointing at `mock`, with the given monitors
+pub fn config(mock: &MockInstatus, monitors: Value) -> Config {
+    config_with(mock, monitors, json!({}))
+}
+
+/// Build a configuration pointing at `mock`, overriding top-level fields with `overrides`
+pub fn config_with(mock: &MockInstatus, monitors: Value, overrides: Value) -> Config {
+    let mut config = json!({
+        "name": PAGE_NAME,
+        "apiKey": API_KEY,
+        "apiBaseUrl": mock.api_url(),
+        "monitors": monitors,
+        "frequency": 1,
+        "maxConnectionTimeout": 5,
+        "incidentMonitoringThreshold": 1,
+    });
+
+    for (key, value) in overrides.as_object().unwrap() {
+        config[key] = value.clone();
+    }
+
+    Config::from_json(&config.to_string()).unwrap()
+}
+
+/// A velocity instance running in a background thread
+pub struct Instance {
+    shutdown: Shutdown,
+    events: Arc<Mutex<Vec<Event>>>,
+    thread: JoinHandle<Result<Summary>>,
+}
+
+impl Instance {
+    /// Every event emitted so far
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Request a graceful shutdown and wait for the monitor loop to finish
+    pub fn stop(self) -> Summary {
+        self.shutdown.trigger();
+        self.thread.join().unwrap().unwrap()
+    }
+
+    /// Wait for the monitor loop to stop on its own
+    pub fn join(self) -> Result<Summary> {
+        self.thread.join().unwrap()
+    }
+}
+
+/// Connect to the status page and run the monitor loop in a background thread
+pub fn spawn_velocity(config: Config) -> Instance {
+    let shutdown = Shutdown::new();
+    let handle = shutdown.clone();
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let recorded = events.clone();
+
+    let thread = std::thread::spawn(move || {
+        smol::block_on(async move {
+            Velocity::connect(config)
+                .await?
+                .on_event(move |event| recorded.lock().unwrap().push(event.clone()))
+                .run(handle)
+                .await
+        })
+    });
+
+    Instance {
+        shutdown,
+        events,
+        thread,
+    }
+}
+
+/// Start a TCP server which greets connections with `220 velocity ESMTP` and
+/// answers any data containing `PING` with `+PONG`
+pub fn start_tcp_server() -> SocketAddr {
+    let listener = smol::block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        smol::block_on(async {
+            loop {
+                let (mut stream, _) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(_) => continue,
+                };
+
+                smol::spawn(async move {
+                    use futures::{AsyncReadExt, AsyncWriteExt};
+
+                    let _ = stream.write_all(b"220 velocity ESMTP\r\n").await;
+
+                    let mut buf = [0; 1024];
+
+                    while let Ok(read) = stream.read(&mut buf).await {
+                        if read == 0 {
+                            break;
+                        }
+
+                        if String::from_utf8_lossy(&buf[..read]).contains("PING") {
+                            let _ = stream.write_all(b"+PONG\r\n").await;
+                        }
+                    }
+                })
+                .detach();
+            }
+        })
+    });
+
+    addr
+}
+
+/// An address nothing is listening on
+pub fn closed_addr() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+    listener.local_addr().unwrap()
+}
+
+/// Start a DNS server answering for a few names under `velocity.test`,
+/// with NXDOMAIN for any other name
+///
+/// `slow.velocity.test` resolves after 300ms.
+pub fn start_dns_server() -> SocketAddr {
+    use simple_dns::{
+        rdata::{RData, A, CNAME, MX, TXT},
+        Name, Packet, ResourceRecord, CLASS, QTYPE, RCODE, TYPE,
+    };
+
+    let socket = smol::block_on(smol::net::UdpSocket::bind("127.0.0.1:0")).unwrap();
+    let addr = socket.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        smol::block_on(async {
+            let mut buf = [0; 4096];
+
+            loop {
+                let (len, from) = match socket.recv_from(&mut buf).await {
+                    Ok(received) => received,
+                    Err(_) => continue,
+                };
+
+                let query = match Packet::parse(&buf[..len]) {
+                    Ok(query) => query,
+                    Err(_) => continue,
+                };
+
+                let question = query.questions[0].clone();
+                let name = question.qname.to_string();
+
+                let records: Vec<RData> = match name.as_str() {
+                    "api.velocity.test" | "slow.velocity.test" => vec![
+                        RData::A(A {
+                            address: u32::from(std::net::Ipv4Addr::new(10, 0, 0, 1)),
+                        }),
+                        RData::A(A {
+                            address: u32::from(std::net::Ipv4Addr::new(10, 0, 0, 2)),
+                        }),
+                    ],
+                    "www.velocity.test" => vec![RData::CNAME(CNAME(Name::new_unchecked(
+                        "api.velocity.test",
+                    )))],
+                    "velocity.test" => vec![
+                        RData::MX(MX {
+                            preference: 10,
+                            exchange: Name::new_unchecked("mail.velocity.test"),
+                        }),
+                        RData::TXT(TXT::new().with_string("v=spf1 -all").unwrap()),
+                    ],
+                    _ => vec![],
+                };
+
+                if name == "slow.velocity.test" {
+                    smol::Timer::after(Duration::from_millis(300)).await;
+                }
+
+                let mut reply = Packet::new_reply(query.id());
+                reply.questions.push(question.clone());
+
+                if !name.ends_with("velocity.test")
+                    || (name != "velocity.test" && records.is_empty())
+                {
+                    *reply.rcode_mut() = RCODE::NameError;
+                }
+
+                for rdata in records {
+                    if QTYPE::from(rdata.type_code()) == question.qtype
+                        || rdata.type_code() == TYPE::CNAME
+                    {
+                        reply.answers.push(ResourceRecord::new(
+                            question.qname.clone(),
+                            CLASS::IN,
+                            60,
+                            rdata,
+                        ));
+                    }
+                }
+
+                let _ = socket
+                    .send_to(&reply.build_bytes_vec().unwrap(), from)
+                    .await;
+            }
+        })
+    });
+
+    addr
+}
+
+/// A TLS server presenting a certificate issued by its own authority
+pub struct TlsServer {
+    pub addr: SocketAddr,
+    /// PEM file of the authority which issued the certificate
+    pub ca_path: PathBuf,
+}
+
+/// Server configuration presenting a certificate for `name` expiring in `days` days,
+/// along with the PEM of the authority which issued it
+fn tls_config(name: &str, days: i64) -> (rustls::ServerConfig, String) {
+    use chrono::{Datelike, Utc};
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa};
+
+    let mut ca_params = CertificateParams::new(vec![]);
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = Certificate::from_params(ca_params).unwrap();
+
+    let expiry = (Utc::now() + chrono::Duration::days(days)).date_naive();
+    let mut params = CertificateParams::new(vec![name.to_string()]);
+    params.not_after =
+        rcgen::date_time_ymd(expiry.year(), expiry.month() as u8, expiry.day() as u8);
+    let leaf = Certificate::from_params(params).unwrap();
+
+    let mut config = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+    config
+        .set_single_cert(
+            vec![
+                rustls::Certificate(leaf.serialize_der_with_signer(&ca).unwrap()),
+                rustls::Certificate(ca.serialize_der().unwrap()),
+            ],
+            rustls::PrivateKey(leaf.serialize_private_key_der()),
+        )
+        .unwrap();
+
+    (config, ca.serialize_pem().unwrap())
+}
+
+/// Bind a TLS listener, writing the PEM of its authority to a temporary file
+fn bind_tls(ca: &str) -> (TcpListener, TlsServer) {
+    let listener = smol::block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let ca_path = std::env::temp_dir().join(format!("velocity-test-ca-{}.pem", addr.port()));
+    std::fs::write(&ca_path, ca).unwrap();
+
+    (listener, TlsServer { addr, ca_path })
+}
+
+/// Start a TLS server with a certificate for `name` expiring in `days` days, negative if already expired
+pub fn start_tls_server(name: &str, days: i64) -> TlsServer {
+    let (config, ca) = tls_config(name, days);
+    let acceptor = async_tls::TlsAcceptor::from(Arc::new(config));
+
+    let (listener, server) = bind_tls(&ca);
+
+    std::thread::spawn(move || {
+        smol::block_on(async {
+            loop {
+                let (stream, _) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(_) => continue,
+                };
+
+                let acceptor = acceptor.clone();
+
+                smol::spawn(async move {
+                    use futures::AsyncReadExt;
+
+                    // keep the connection open until the client is done with it
+                    if let Ok(mut stream) = acceptor.accept(stream).await {
+                        let _ = stream.read(&mut [0; 1024]).await;
+                    }
+                })
+                .detach();
+            }
+        })
+    });
+
+    server
+}
+
+/// Start a plaintext gRPC server implementing the health checking protocol
+///
+/// The server is SERVING, the `db` service NOT_SERVING, `cache` UNKNOWN and
+/// any other service is not found.
+pub fn start_grpc_server() -> SocketAddr {
+    let listener = smol::block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        smol::block_on(async {
+            while let Ok((stream, _)) = listener.accept().await {
+                smol::spawn(serve_grpc(stream)).detach();
+            }
+        })
+    });
+
+    addr
+}
+
+/// Start a gRPC server like [`start_grpc_server`] over TLS, with a certificate for `localhost`
+pub fn start_grpc_tls_server() -> TlsServer {
+    let (mut config, ca) = tls_config("localhost", 365);
+    config.set_protocols(&[b"h2".to_vec()]);
+    let acceptor = async_tls::TlsAcceptor::from(Arc::new(config));
+
+    let (listener, server) = bind_tls(&ca);
+
+    std::thread::spawn(move || {
+        smol::block_on(async {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+
+                smol::spawn(async move {
+                    if let Ok(stream) = acceptor.accept(stream).await {
+                        serve_grpc(stream).await;
+                    }
+                })
+                .detach();
+            }
+        })
+    });
+
+    server
+}
+
+async fn serve_grpc<S>(stream: S)
+where
+    S: futures::AsyncRead + futures::AsyncWrite + Unpin + Send + 'static,
+{
+    let mut connection = match h2::server::handshake(async_compat::Compat::new(stream)).await {
+        Ok(connection) => connection,
+        Err(_) => return,
+    };
+
+    while let Some(Ok((request, respond))) = connection.accept().await {
+        smol::spawn(health_check(request, respond)).detach();
+    }
+}
+
+async fn health_check(
+    request: http::Request<h2::RecvStream>,
+    mut respond: h2::server::SendResponse<bytes::Bytes>,
+) {
+    let mut body = request.into_body();
+    let mut frame = vec![];
+
+    while let Some(Ok(chunk)) = body.data().await {
+        let _ = body.flow_control().release_capacity(chunk.len());
+        frame.extend_from_slice(&chunk);
+    }
+
+    // skip the frame header, then the key and length of the service name
+    let service = String::from_utf8_lossy(frame.get(7..).unwrap_or_default()).to_string();
+
+    let status = match service.as_str() {
+        "" => 1,
+        "db" => 2,
+        "cache" => 0,
+        _ => {
+            let response = http::Response::builder()
+                .header("content-type", "application/grpc")
+                .header("grpc-status", "5")
+                .header("grpc-message", "unknown service")
+                .body(())
+                .unwrap();
+
+            let _ = respond.send_response(response, true);
+
+            return;
+        }
+    };
+
+    let response = http::Response::builder()
+        .header("content-type", "application/grpc")
+        .body(())
+        .unwrap();
+
+    let mut stream = match respond.send_response(response, false) {
+        Ok(stream) => stream,
+        Err(_) => return,
+    };
+
+    // UNKNOWN is the default value, which is left out of the message
+    let message: &[u8] = if status == 0 { &[] } else { &[0x08, status] };
+
+    let mut frame = vec![0];
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(message);
+
+    let mut trailers = http::HeaderMap::new();
+    trailers.insert("grpc-status", "0".parse().unwrap());
+
+    let _ = stream.send_data(frame.into(), false);
+    let _ = stream.send_trailers(trailers);
+}
+
+/// Start a WebSocket server echoing every message it receives
+pub fn start_websocket_server() -> SocketAddr {
+    let listener = smol::block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        smol::block_on(async {
+            while let Ok((stream, _)) = listener.accept().await {
+                smol::spawn(echo(stream)).detach();
+            }
+        })
+    });
+
+    addr
+}
+
+/// Start a WebSocket server like [`start_websocket_server`] over TLS, with a certificate for `localhost`
+pub fn start_websocket_tls_server() -> TlsServer {
+    let (config, ca) = tls_config("localhost", 365);
+    let acceptor = async_tls::TlsAcceptor::from(Arc::new(config));
+
+    let (listener, server) = bind_tls(&ca);
+
+    std::thread::spawn(move || {
+        smol::block_on(async {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+
+                smol::spawn(async move {
+                    if let Ok(stream) = acceptor.accept(stream).await {
+                        echo(stream).await;
+                    }
+                })
+                .detach();
+            }
+        })
+    });
+
+    server
+}
+
+async fn echo<S>(stream: S)
+where
+    S: futures::AsyncRead + futures::AsyncWrite + Unpin,
+{
+    use futures::{SinkExt, StreamExt};
+
+    let mut socket = match async_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(_) => return,
+    };
+
+    while let Some(Ok(message)) = socket.next().await {
+        if message.is_text() || message.is_binary() {
+            let _ = socket.send(message).await;
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use serde_json::{json, Value};
+use velocity::config::{Config, API_BASE_URL_ENV, DEFAULT_API_BASE_URL};
+
+/// Parse a configuration with the given monitors
+fn config(monitors: Value) -> Config {
+    config_with(monitors, json!({}))
+}
+
+/// Parse a configuration with the given monitors, overriding top-level fields with `overrides`
+fn config_with(monitors: Value, overrides: Value) -> Config {
+    let mut config = json!({
+        "name": "Velocity",
+        "apiKey": "key",
+        "monitors": monitors,
+        "frequency": 10,
+    });
+
+    for (key, value) in overrides.as_object().unwrap() {
+        config[key] = value.clone();
+    }
+
+    Config::from_json(&config.to_string()).unwrap()
+}
+
+#[test]
+fn api_base_url() {
+    std::env::remove_var(API_BASE_URL_ENV);
+
+    let overridden = || {
+        config_with(
+            json!({}),
+            json!({ "apiBaseUrl": "http://localhost:8080/v1" }),
+        )
+    };
+
+    assert_eq!(
+        config(json!({})).api_base_url.unwrap(),
+        DEFAULT_API_BASE_URL
+    );
+    assert_eq!(
+        overridden().api_base_url.unwrap(),
+        "http://localhost:8080/v1"
+    );
+
+    std::env::set_var(API_BASE_URL_ENV, "http://mock:1234/v1");
+
+    assert_eq!(
+        config(json!({})).api_base_url.unwrap(),
+        "http://mock:1234/v1"
+    );
+    assert_eq!(overridden().api_base_url.unwrap(), "http://mock:1234/v1");
+
+    std::env::remove_var(API_BASE_URL_ENV);
+}
+
+#[test]
+fn lint() {
+    assert_eq!(config(json!({})).lint(), vec!["no monitors are configured"]);
+
+    let config = config_with(
+        json!({
+            "API": { "url": "https://example.com", "type": "uptime", "frequency": 0 },
+            "FTP": { "url": "ftp://example.com", "type": "uptime" },
+            "Web": { "url": "not a url", "type": "latency", "jitter": 10 },
+        }),
+        json!({ "apiKey": "" }),
+    );
+
+    assert_eq!(
+        config.lint(),
+        vec![
+            "apiKey is empty",
+            "API: frequency is 0, the endpoint would be checked continuously",
+            "FTP: unsupported URL scheme ftp, expected http or https",
+            "Web: invalid URL not a url: relative URL without a base",
+            "Web: jitter of 10s is not shorter than its frequency of 10s",
+        ]
+    );
+}
+
+#[test]
+fn lint_secrets_and_body_files() {
+    let config = config(json!({
+        "API": {
+            "url": "https://example.com",
+            "type": "uptime",
+            "body": { "file": "/nonexistent/body.json" },
+            "auth": { "type": "bearer", "token": { "env": "VELOCITY_LINT_UNSET" } },
+        },
+    }));
+
+    assert_eq!(
+        config.lint(),
+        vec![
+            "API: environment variable VELOCITY_LINT_UNSET is not set",
+            "API: body file /nonexistent/body.json does not exist",
+        ]
+    );
+}
+
+#[test]
+fn lint_certificate_monitors() {
+    let config = config(json!({
+        "API": { "url": "https://example.com", "type": "certificate" },
+        "Internal": {
+            "url": "internal.example.com:8443",
+            "type": "certificate",
+            "caCertificate": "/nonexistent/ca.pem",
+        },
+        "Invalid": { "url": "example.com:https", "type": "certificate" },
+    }));
+
+    assert_eq!(
+        config.lint(),
+        vec![
+            "Internal: CA certificate /nonexistent/ca.pem does not exist",
+            "Invalid: invalid address example.com:https, expected host, host:port or a URL",
+        ]
+    );
+}
+
+#[test]
+fn lint_reachability_monitors() {
+    let config = config(json!({
+        "Gateway": { "url": "gateway:22", "type": "reachability", "attempts": 0, "maxLoss": 120 },
+        "Invalid": { "url": "gateway", "type": "reachability" },
+    }));
+
+    assert_eq!(
+        config.lint(),
+        vec![
+            "Gateway: attempts is 0, a single handshake will be timed",
+            "Gateway: maxLoss of 120% is not between 0 and 100",
+            "Invalid: invalid address gateway, expected host:port",
+        ]
+    );
+}
+
+#[test]
+fn lint_heartbeat_monitors() {
+    let config = config(json!({
+        "Backups": { "url": "/jobs/backups", "type": "heartbeat", "gracePeriod": 0 },
+        "Reports": { "url": "jobs/backups/", "type": "heartbeat" },
+    }));
+
+    assert_eq!(
+        config.lint(),
+        vec![
+            "Backups: gracePeriod is 0, the monitor will be down between pings",
+            "Reports: heartbeat path /jobs/backups is also used by Backups",
+        ]
+    );
+}
+
+#[test]
+fn lint_command_monitors() {
+    let config = config(json!({
+        "Script": { "type": "command", "command": [], "content": { "keywords": ["OK"] } },
+    }));
+
+    assert_eq!(
+        config.lint(),
+        vec![
+            "Script: no command is configured",
+            "Script: content is only checked by uptime and latency monitors",
+        ]
+    );
+}
+
+#[test]
+fn lint_grpc_monitors() {
+    let config = config(json!({
+        "Orders": { "url": "https://orders.internal:50051", "type": "grpc", "service": "orders" },
+        "Users": { "url": "grpc://users.internal:50051", "type": "grpc" },
+    }));
+
+    assert_eq!(
+        config.lint(),
+        vec![
+            "Users: invalid URL grpc://users.internal:50051, expected http://host:port or https://host:port"
+        ]
+    );
+}
+
+#[test]
+fn lint_websocket_monitors() {
+    let config = config(json!({
+        "Chat": { "url": "wss://chat.example.com/socket", "type": "websocket", "probe": "ping", "banner": "pong" },
+        "Feed": { "url": "https://feed.example.com/socket", "type": "websocket" },
+    }));
+
+    assert_eq!(config.monitors["Chat"].metric_name("Chat"), Some("Chat"));
+    assert_eq!(
+        config.lint(),
+        vec![
+            "Feed: invalid URL https://feed.example.com/socket, expected ws://host/path or wss://host/path"
+        ]
+    );
+}
+
+#[test]
+fn lint_flow_monitors() {
+    let config = config(json!({
+        "Checkout": {
+            "type": "flow",
+            "steps": [
+                { "url": "https://example.com/login", "extract": { "token": { "json": "$.token" } } },
+                {
+                    "name": "cart",
+                    "url": "https://example.com/cart/{{cart}}",
+                    "headers": { "Authorization": "Bearer {{token}}" },
+                },
+            ],
+        },
+        "Empty": { "type": "flow", "steps": [] },
+    }));
+
+    assert_eq!(
+        config.lint(),
+        vec![
+            "Checkout: step 2 (cart) uses {{cart}}, which no earlier step extracts",
+            "Empty: no steps are configured",
+        ]
+    );
+}
+
+#[test]
+fn lint_confirmation_thresholds() {
+    let config = config(json!({
+        "API": {
+            "url": "https://example.com",
+            "type": "uptime",
+            "failuresBeforeIncident": 0,
+            "successesBeforeRecovery": 0,
+            "flapDetection": { "changes": 5, "checks": 5 },
+        },
+        "Web": {
+            "url": "https://example.com",
+            "type": "uptime",
+            "flapDetection": { "changes": 0, "checks": 5 },
+        },
+    }));
+
+    assert_eq!(
+        config.lint(),
+        vec![
+            "API: failuresBeforeIncident is 0, a single failure opens an incident",
+            "API: successesBeforeRecovery is 0, a single success starts recovery",
+            "API: flapDetection can never detect flapping, 5 checks change state at most 4 times",
+            "Web: flapDetection.changes is 0, the first change would count as flapping",
+        ]
+    );
+}
+
+#[test]
+fn lint_latency_threshold() {
+    let config = config(json!({
+        "API": {
+            "url": "https://example.com",
+            "type": "latency",
+            "latencyThreshold": { "warning": 500, "window": 3 },
+        },
+        "Web": {
+            "url": "https://example.com",
+            "type": "latency",
+            "latencyThreshold": { "warning": 500, "window": 0 },
+        },
+    }));
+
+    assert_eq!(
+        config.lint(),
+        vec!["Web: latencyThreshold.window is 0, a single slow check is reported as degraded"]
+    );
+}
@@ -0,0 +1,1598 @@
+mod common;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use common::MockInstatus;
+use serde_json::json;
+use velocity::{
+    check::Severity, check_all, heartbeat::Heartbeats, net, Config, Event, Velocity, VelocityError,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+#[test]
+fn incident_lifecycle() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.set_healthy(false);
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({ "API": { "url": mock.health_url(), "type": "uptime" } }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    let incident = &mock.incidents()[0];
+    assert_eq!(incident.name, "API Issues");
+    assert_eq!(incident.status, "IDENTIFIED");
+    assert_eq!(incident.components, vec!["component-1".to_string()]);
+    assert_eq!(
+        incident.statuses,
+        vec![("component-1".to_string(), "MAJOROUTAGE".to_string())]
+    );
+
+    mock.set_healthy(true);
+
+    mock.wait_for(TIMEOUT, "incident to be monitored", |mock| {
+        mock.incidents()[0].status == "MONITORING"
+    });
+
+    mock.wait_for(TIMEOUT, "incident to be resolved", |mock| {
+        mock.incidents()[0].status == "RESOLVED"
+    });
+
+    let incidents = mock.incidents();
+    assert_eq!(incidents.len(), 1);
+    assert_eq!(incidents[0].updates, vec!["MONITORING", "RESOLVED"]);
+    assert_eq!(
+        incidents[0].statuses,
+        vec![("component-1".to_string(), "OPERATIONAL".to_string())]
+    );
+}
+
+#[test]
+fn configs_without_defaults_filled_in_can_run() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.set_healthy(false);
+
+    // deserialized directly, so optional settings are left unset
+    let config: Config = serde_json::from_value(json!({
+        "name": common::PAGE_NAME,
+        "apiKey": common::API_KEY,
+        "apiBaseUrl": mock.api_url(),
+        "monitors": { "API": { "url": mock.health_url(), "type": "uptime" } },
+        "frequency": 1,
+    }))
+    .unwrap();
+
+    common::spawn_velocity(config);
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    mock.set_healthy(true);
+
+    mock.wait_for(TIMEOUT, "incident to be monitored", |mock| {
+        mock.incidents()[0].status == "MONITORING"
+    });
+}
+
+#[test]
+fn repeated_failures_open_a_single_incident() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.set_healthy(false);
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({ "API": { "url": mock.health_url(), "type": "uptime" } }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    std::thread::sleep(Duration::from_secs(3));
+
+    assert_eq!(mock.incidents().len(), 1);
+}
+
+#[test]
+fn latency_is_pushed_to_metric() {
+    let mock = MockInstatus::start();
+    mock.add_metric("metric-1", "API Latency");
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({ "API Latency": { "url": mock.health_url(), "type": "latency" } }),
+    ));
+
+    mock.wait_for(TIMEOUT, "latency to be reported", |mock| {
+        mock.metric_points().len() >= 2
+    });
+
+    assert!(mock
+        .metric_points()
+        .iter()
+        .all(|point| point.metric_id == "metric-1" && point.timestamp > 0));
+    assert!(mock.incidents().is_empty());
+}
+
+#[test]
+fn checks_run_concurrently_up_to_cap() {
+    let mock = MockInstatus::start();
+    mock.add_metric("metric-1", "Slow 1");
+    mock.add_metric("metric-2", "Slow 2");
+    mock.add_metric("metric-3", "Slow 3");
+
+    common::spawn_velocity(common::config_with(
+        &mock,
+        json!({
+            "Slow 1": { "url": mock.slow_url(500), "type": "latency" },
+            "Slow 2": { "url": mock.slow_url(500), "type": "latency" },
+            "Slow 3": { "url": mock.slow_url(500), "type": "latency" },
+        }),
+        json!({ "maxConcurrentChecks": 2 }),
+    ));
+
+    mock.wait_for(TIMEOUT, "latency to be reported", |mock| {
+        mock.metric_points().len() >= 3
+    });
+
+    assert_eq!(mock.slow_max_in_flight(), 2);
+}
+
+#[test]
+fn monitors_are_checked_at_their_own_frequency() {
+    let mock = MockInstatus::start();
+    mock.add_metric("metric-1", "Fast");
+    mock.add_metric("metric-2", "Slow");
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "Fast": { "url": mock.health_url(), "type": "latency", "frequency": 1 },
+            "Slow": { "url": mock.health_url(), "type": "latency", "frequency": 60 },
+        }),
+    ));
+
+    mock.wait_for(TIMEOUT, "fast monitor to be checked", |mock| {
+        mock.metric_points()
+            .iter()
+            .filter(|point| point.metric_id == "metric-1")
+            .count()
+            >= 4
+    });
+
+    let slow = mock
+        .metric_points()
+        .iter()
+        .filter(|point| point.metric_id == "metric-2")
+        .count();
+
+    assert_eq!(slow, 1);
+}
+
+#[test]
+fn monitor_timeout_reports_failure() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({ "API": { "url": mock.slow_url(10_000), "type": "uptime", "timeout": 1 } }),
+    ));
+
+    mock.wait_for(
+        Duration::from_secs(5),
+        "incident to be identified",
+        |mock| !mock.incidents().is_empty(),
+    );
+
+    assert_eq!(mock.incidents()[0].status, "IDENTIFIED");
+}
+
+#[test]
+fn shutdown_reports_summary() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.set_healthy(false);
+
+    let velocity = common::spawn_velocity(common::config(
+        &mock,
+        json!({ "API": { "url": mock.health_url(), "type": "uptime" } }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    let summary = velocity.stop();
+
+    assert!(summary.checks >= 1);
+    assert_eq!(summary.checks, summary.failures);
+    assert_eq!(summary.incidents_opened, 1);
+    assert_eq!(summary.incidents_resolved, 0);
+}
+
+#[test]
+fn shutdown_waits_for_in_flight_checks() {
+    let mock = MockInstatus::start();
+    mock.add_metric("metric-1", "Slow");
+
+    let velocity = common::spawn_velocity(common::config(
+        &mock,
+        json!({ "Slow": { "url": mock.slow_url(1500), "type": "latency", "frequency": 60 } }),
+    ));
+
+    mock.wait_for(TIMEOUT, "check to be in flight", |mock| {
+        mock.slow_max_in_flight() == 1
+    });
+
+    let summary = velocity.stop();
+
+    assert_eq!(summary.checks, 1);
+    assert_eq!(mock.metric_points().len(), 1);
+}
+
+#[test]
+fn api_errors_are_retried() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.set_healthy(false);
+    mock.fail_incident_lists(2);
+
+    let velocity = common::spawn_velocity(common::config(
+        &mock,
+        json!({ "API": { "url": mock.health_url(), "type": "uptime" } }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    velocity.stop();
+
+    assert_eq!(mock.incidents().len(), 1);
+}
+
+#[test]
+fn invalid_api_key_is_fatal() {
+    let mock = MockInstatus::start();
+
+    let mut config = common::config(
+        &mock,
+        json!({ "API": { "url": mock.health_url(), "type": "uptime" } }),
+    );
+    config.api_key = "invalid".to_string();
+
+    let err = common::spawn_velocity(config).join().unwrap_err();
+
+    assert!(err.is_fatal());
+    assert!(matches!(err, VelocityError::Api { .. }));
+}
+
+#[test]
+fn rejected_incidents_are_fatal() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.set_healthy(false);
+    mock.reject_incidents(403);
+
+    let err = common::spawn_velocity(common::config(
+        &mock,
+        json!({ "API": { "url": mock.health_url(), "type": "uptime" } }),
+    ))
+    .join()
+    .unwrap_err();
+
+    assert!(err.is_fatal());
+    assert!(matches!(err, VelocityError::Api { .. }));
+}
+
+#[test]
+fn incidents_are_listed_once_per_cycle() {
+    let mock = MockInstatus::start();
+
+    // the monitor is checked three times per cycle of the configuration
+    let velocity = common::spawn_velocity(common::config_with(
+        &mock,
+        json!({ "API": { "url": mock.health_url(), "type": "uptime", "frequency": 1 } }),
+        json!({ "frequency": 3 }),
+    ));
+
+    mock.wait_for(TIMEOUT, "monitor to be checked 5 times", |_| {
+        velocity
+            .events()
+            .iter()
+            .filter(|event| matches!(event, Event::Checked(_)))
+            .count()
+            >= 5
+    });
+
+    assert_eq!(mock.incident_lists(), 2);
+}
+
+#[test]
+fn missing_metric_is_fatal() {
+    let mock = MockInstatus::start();
+
+    let err = common::spawn_velocity(common::config(
+        &mock,
+        json!({ "API": { "url": mock.health_url(), "type": "latency" } }),
+    ))
+    .join()
+    .unwrap_err();
+
+    assert!(matches!(err, VelocityError::MissingMetric(name) if name == "API"));
+}
+
+#[test]
+fn missing_components_are_fatal() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+
+    let err = common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "API": { "url": mock.health_url(), "type": "uptime", "components": ["API", "Dashboard"] },
+            "Website": { "url": mock.health_url(), "type": "uptime", "components": ["component-2"] },
+            "Docs": { "url": mock.health_url(), "type": "uptime" },
+        }),
+    ))
+    .join()
+    .unwrap_err();
+
+    assert!(err.is_fatal());
+    assert!(matches!(
+        &err,
+        VelocityError::MissingComponents(missing)
+            if missing == &["API: Dashboard".to_string(), "Website: component-2".to_string()]
+    ));
+    assert_eq!(
+        err.to_string(),
+        "could not find components API: Dashboard, Website: component-2"
+    );
+}
+
+#[test]
+fn monitors_affect_components_listed_by_id() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.add_component("component-2", "Website");
+    mock.set_healthy(false);
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "Backend": {
+                "url": mock.health_url(),
+                "type": "uptime",
+                "components": ["component-1", "Website"],
+            },
+        }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    let incident = &mock.incidents()[0];
+
+    assert_eq!(incident.name, "Backend Issues");
+    assert_eq!(
+        incident.components,
+        vec!["component-1".to_string(), "component-2".to_string()]
+    );
+}
+
+#[test]
+fn setup_events_are_emitted() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let recorded = events.clone();
+
+    smol::block_on(Velocity::connect_with_events(
+        common::config(
+            &mock,
+            json!({ "API": { "url": mock.health_url(), "type": "uptime" } }),
+        ),
+        move |event| recorded.lock().unwrap().push(event.clone()),
+    ))
+    .unwrap();
+
+    assert!(matches!(
+        events.lock().unwrap().as_slice(),
+        [Event::Connecting { host }] if host == "127.0.0.1"
+    ));
+}
+
+#[test]
+fn events_are_emitted() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.set_healthy(false);
+    mock.fail_incident_lists(1);
+
+    let velocity = common::spawn_velocity(common::config(
+        &mock,
+        json!({ "API": { "url": mock.health_url(), "type": "uptime" } }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    mock.set_healthy(true);
+
+    mock.wait_for(TIMEOUT, "incident to be resolved", |mock| {
+        mock.incidents()[0].status == "RESOLVED"
+    });
+
+    mock.wait_for(TIMEOUT, "resolution to be reported", |_| {
+        velocity
+            .events()
+            .iter()
+            .any(|event| matches!(event, Event::IncidentResolved { .. }))
+    });
+
+    let events = velocity.events();
+    velocity.stop();
+
+    assert!(matches!(events[0], Event::Started));
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, Event::Error { .. })));
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, Event::Retrying { .. })));
+    assert!(events.iter().any(
+        |event| matches!(event, Event::Checked(result) if result.name == "API" && !result.is_success())
+    ));
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, Event::IncidentOpened { name, .. } if name == "API")));
+    assert!(events.iter().any(
+        |event| matches!(event, Event::IncidentMonitoring { incident_id, .. } if incident_id == "incident-1")
+    ));
+    assert!(events.iter().any(
+        |event| matches!(event, Event::IncidentResolved { incident_id, .. } if incident_id == "incident-1")
+    ));
+}
+
+#[test]
+fn check_all_reports_every_monitor() {
+    let mock = MockInstatus::start();
+    mock.set_healthy(false);
+
+    let config = common::config(
+        &mock,
+        json!({
+            "API": { "url": mock.health_url(), "type": "uptime" },
+            "Slow": { "url": mock.slow_url(10), "type": "latency" },
+            "Backups": { "url": "/jobs/backups", "type": "heartbeat" },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+
+    // heartbeats are only received while monitoring, so they aren't checked
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].name, "API");
+    assert_eq!(results[0].failure.as_deref(), Some("unexpected status 503"));
+    assert_eq!(results[1].name, "Slow");
+    assert!(results[1].is_success());
+}
+
+#[test]
+fn assertions_are_checked_against_responses() {
+    let mock = MockInstatus::start();
+    mock.set_response(
+        200,
+        &[("Content-Type", "application/json")],
+        r#"{"status":"maintenance"}"#,
+    );
+
+    let check = |assertions: serde_json::Value| {
+        let config = common::config(
+            &mock,
+            json!({ "API": { "url": mock.custom_url(), "type": "uptime", "assertions": assertions } }),
+        );
+
+        smol::block_on(check_all(&net::build_client(None).unwrap(), &config))
+            .remove(0)
+            .failure
+    };
+
+    assert_eq!(check(json!({ "status": ["2xx"] })), None);
+    assert_eq!(
+        check(json!({ "json": { "$.status": "ok" } })).as_deref(),
+        Some("expected $.status to be \"ok\", got \"maintenance\"")
+    );
+    assert_eq!(
+        check(json!({ "headers": { "content-type": "application/json", "X-Version": null } }))
+            .as_deref(),
+        Some("missing header X-Version")
+    );
+    assert_eq!(
+        check(json!({ "headers": { "Content-Type": "text/html" } })).as_deref(),
+        Some("header Content-Type is \"application/json\", expected \"text/html\"")
+    );
+
+    mock.set_response(500, &[], "");
+
+    assert_eq!(
+        check(json!({ "status": [200, "3xx"] })).as_deref(),
+        Some("unexpected status 500, expected 200, 3xx")
+    );
+}
+
+#[test]
+fn requests_are_configurable() {
+    let mock = MockInstatus::start();
+
+    std::env::set_var("VELOCITY_TEST_TOKEN", "secret-token");
+    std::env::set_var("VELOCITY_TEST_PASSWORD", "secret-password");
+
+    let body = std::env::temp_dir().join("velocity-test-body.xml");
+    std::fs::write(&body, "<ping/>").unwrap();
+
+    let config = common::config(
+        &mock,
+        json!({
+            "Bearer": {
+                "url": mock.echo_url(),
+                "type": "uptime",
+                "method": "post",
+                "headers": { "X-Health-Key": "key", "Pragma": "cache" },
+                "body": { "json": { "deep": true } },
+                "auth": { "type": "bearer", "token": { "env": "VELOCITY_TEST_TOKEN" } },
+            },
+            "Basic": {
+                "url": mock.echo_url(),
+                "type": "uptime",
+                "method": "PUT",
+                "body": { "file": body },
+                "auth": {
+                    "type": "basic",
+                    "username": "velocity",
+                    "password": { "env": "VELOCITY_TEST_PASSWORD" },
+                },
+            },
+            "Missing": {
+                "url": mock.echo_url(),
+                "type": "uptime",
+                "headers": { "X-Health-Key": { "env": "VELOCITY_TEST_MISSING" } },
+            },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+
+    assert!(results[0].is_success());
+    assert!(results[1].is_success());
+    assert_eq!(results[2].name, "Missing");
+    assert_eq!(
+        results[2].failure.as_deref(),
+        Some("environment variable VELOCITY_TEST_MISSING is not set")
+    );
+
+    let mut requests = mock.echoed();
+    requests.sort_by(|a, b| a.method.cmp(&b.method));
+
+    assert_eq!(requests.len(), 2);
+
+    let bearer = &requests[0];
+    assert_eq!(bearer.method, "POST");
+    assert_eq!(bearer.body, r#"{"deep":true}"#);
+    assert_eq!(bearer.header("Authorization"), Some("Bearer secret-token"));
+    assert_eq!(bearer.header("X-Health-Key"), Some("key"));
+    assert_eq!(bearer.header("Pragma"), Some("cache"));
+    assert_eq!(bearer.header("Content-Type"), Some("application/json"));
+
+    let basic = &requests[1];
+    assert_eq!(basic.method, "PUT");
+    assert_eq!(basic.body, "<ping/>");
+    assert_eq!(
+        basic.header("Authorization"),
+        Some("Basic dmVsb2NpdHk6c2VjcmV0LXBhc3N3b3Jk")
+    );
+    assert_eq!(basic.header("Pragma"), Some("no-cache"));
+}
+
+#[test]
+fn tcp_monitors_connect_probe_and_match_banners() {
+    let mock = MockInstatus::start();
+    let server = common::start_tcp_server();
+    let closed = common::closed_addr();
+
+    let config = common::config(
+        &mock,
+        json!({
+            "Connect": { "url": server.to_string(), "type": "tcp" },
+            "Closed": { "url": format!("tcp://{}", closed), "type": "tcp" },
+            "Redis": { "url": format!("tcp://{}", server), "type": "tcp", "probe": "PING\r\n", "banner": "+PONG" },
+            "SMTP": { "url": format!("tcp://{}", server), "type": "tcp", "banner": "220 velocity" },
+            "Wrong": { "url": format!("tcp://{}", server), "type": "tcp", "banner": "+OK", "timeout": 1 },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+    let failure = |name: &str| {
+        results
+            .iter()
+            .find(|result| result.name == name)
+            .unwrap()
+            .failure
+            .clone()
+    };
+
+    assert_eq!(failure("Connect"), None);
+    assert_eq!(failure("Redis"), None);
+    assert_eq!(failure("SMTP"), None);
+    assert!(failure("Closed")
+        .unwrap()
+        .starts_with(&format!("failed to connect to {}", closed)));
+    assert_eq!(
+        failure("Wrong").as_deref(),
+        Some(r#"expected banner "+OK", got "220 velocity ESMTP\r\n""#)
+    );
+}
+
+#[test]
+fn tcp_monitors_open_incidents_and_report_latency() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "Database");
+    mock.add_metric("metric-1", "Redis Latency");
+
+    let server = common::start_tcp_server();
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "Database": { "url": common::closed_addr().to_string(), "type": "tcp" },
+            "Redis": { "url": server.to_string(), "type": "tcp", "metric": "Redis Latency" },
+        }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+    mock.wait_for(TIMEOUT, "latency to be reported", |mock| {
+        !mock.metric_points().is_empty()
+    });
+
+    assert_eq!(mock.incidents()[0].name, "Database Issues");
+    assert_eq!(mock.metric_points()[0].metric_id, "metric-1");
+}
+
+#[test]
+fn dns_monitors_check_answers_and_resolution_time() {
+    let mock = MockInstatus::start();
+    let resolver = common::start_dns_server().to_string();
+
+    let config = common::config(
+        &mock,
+        json!({
+            "A": { "url": "api.velocity.test", "type": "dns", "resolver": resolver, "answers": ["10.0.0.2"] },
+            "CNAME": { "url": "www.velocity.test", "type": "dns", "resolver": resolver, "recordType": "CNAME", "answers": ["API.velocity.test."] },
+            "MX": { "url": "velocity.test", "type": "dns", "resolver": resolver, "recordType": "MX", "answers": ["10 mail.velocity.test"] },
+            "TXT": { "url": "velocity.test", "type": "dns", "resolver": resolver, "recordType": "TXT", "answers": ["v=spf1 -all"] },
+            "Missing": { "url": "missing.velocity.test", "type": "dns", "resolver": resolver },
+            "NoRecords": { "url": "velocity.test", "type": "dns", "resolver": resolver, "recordType": "AAAA" },
+            "Slow": { "url": "slow.velocity.test", "type": "dns", "resolver": resolver, "maxResolutionTime": 100 },
+            "Wrong": { "url": "api.velocity.test", "type": "dns", "resolver": resolver, "answers": ["10.0.0.3"] },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+    let failure = |name: &str| {
+        results
+            .iter()
+            .find(|result| result.name == name)
+            .unwrap()
+            .failure
+            .clone()
+    };
+
+    assert_eq!(failure("A"), None);
+    assert_eq!(failure("CNAME"), None);
+    assert_eq!(failure("MX"), None);
+    assert_eq!(failure("TXT"), None);
+    assert_eq!(
+        failure("Missing"),
+        Some(format!(
+            "{} answered NXDOMAIN for missing.velocity.test",
+            resolver
+        ))
+    );
+    assert_eq!(
+        failure("NoRecords").as_deref(),
+        Some("no AAAA records for velocity.test")
+    );
+    assert!(failure("Slow")
+        .unwrap()
+        .ends_with("longer than the maximum of 100 ms"));
+    assert_eq!(
+        failure("Wrong").as_deref(),
+        Some("expected api.velocity.test to resolve to 10.0.0.3, got 10.0.0.1, 10.0.0.2")
+    );
+}
+
+#[test]
+fn certificate_monitors_report_expiry_and_validity() {
+    let mock = MockInstatus::start();
+
+    let valid = common::start_tls_server("localhost", 365);
+    let expiring = common::start_tls_server("localhost", 5);
+    let expired = common::start_tls_server("localhost", -2);
+    let mismatched = common::start_tls_server("velocity.test", 365);
+
+    let monitor = |server: &common::TlsServer| {
+        json!({
+            "url": format!("localhost:{}", server.addr.port()),
+            "type": "certificate",
+            "caCertificate": server.ca_path,
+        })
+    };
+
+    let config = common::config(
+        &mock,
+        json!({
+            "Valid": monitor(&valid),
+            "Expiring": monitor(&expiring),
+            "Expired": monitor(&expired),
+            "Mismatched": monitor(&mismatched),
+            "Untrusted": { "url": format!("https://localhost:{}/", valid.addr.port()), "type": "certificate" },
+            "Threshold": { "url": format!("localhost:{}", expiring.addr.port()), "type": "certificate", "caCertificate": expiring.ca_path, "expiryThreshold": 2 },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+    let result = |name: &str| results.iter().find(|result| result.name == name).unwrap();
+
+    assert_eq!(result("Valid").failure, None);
+    assert!(result("Valid")
+        .detail
+        .as_deref()
+        .unwrap()
+        .starts_with("certificate expires in 36"));
+    assert_eq!(result("Threshold").failure, None);
+
+    assert!(result("Expiring")
+        .failure
+        .as_deref()
+        .unwrap()
+        .ends_with("within the threshold of 14 days"));
+    assert_eq!(result("Expiring").severity, Severity::Degraded);
+
+    assert_eq!(
+        result("Expired").failure.as_deref(),
+        Some("certificate has expired")
+    );
+    assert_eq!(
+        result("Mismatched").failure.as_deref(),
+        Some("certificate is not valid for localhost")
+    );
+    assert_eq!(
+        result("Untrusted").failure.as_deref(),
+        Some("certificate is not issued by a trusted authority")
+    );
+
+    for name in ["Expired", "Mismatched", "Untrusted"] {
+        assert_eq!(result(name).severity, Severity::Outage);
+    }
+}
+
+#[test]
+fn certificate_monitors_open_degraded_and_outage_incidents() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.add_component("component-2", "Dashboard");
+
+    let expiring = common::start_tls_server("localhost", 5);
+    let mismatched = common::start_tls_server("velocity.test", 365);
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "API": { "url": format!("localhost:{}", expiring.addr.port()), "type": "certificate", "caCertificate": expiring.ca_path },
+            "Dashboard": { "url": format!("localhost:{}", mismatched.addr.port()), "type": "certificate", "caCertificate": mismatched.ca_path },
+        }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incidents to be identified", |mock| {
+        mock.incidents().len() == 2
+    });
+
+    let incidents = mock.incidents();
+    let incident = |name: &str| {
+        incidents
+            .iter()
+            .find(|incident| incident.name == name)
+            .unwrap()
+    };
+
+    assert_eq!(
+        incident("API Issues").statuses,
+        vec![("component-1".to_string(), "DEGRADEDPERFORMANCE".to_string())]
+    );
+    assert!(incident("API Issues")
+        .message
+        .contains("degraded performance"));
+    assert_eq!(
+        incident("Dashboard Issues").statuses,
+        vec![("component-2".to_string(), "MAJOROUTAGE".to_string())]
+    );
+}
+
+#[test]
+fn reachability_monitors_time_handshakes() {
+    let mock = MockInstatus::start();
+    let server = common::start_tcp_server();
+
+    let config = common::config(
+        &mock,
+        json!({
+            "Up": { "url": server.to_string(), "type": "reachability", "attempts": 3 },
+            "Down": { "url": common::closed_addr().to_string(), "type": "reachability", "attempts": 3 },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+
+    assert_eq!(results[1].name, "Up");
+    assert_eq!(results[1].failure, None);
+    assert!(results[1]
+        .detail
+        .as_deref()
+        .unwrap()
+        .starts_with("min/avg/max "));
+    assert!(results[1].detail.as_deref().unwrap().ends_with(", 0% loss"));
+
+    assert_eq!(results[0].name, "Down");
+    assert!(results[0]
+        .failure
+        .as_deref()
+        .unwrap()
+        .contains("is unreachable, all 3 attempts failed"));
+    assert_eq!(results[0].detail, None);
+}
+
+#[test]
+fn reachability_monitors_report_average_to_metric() {
+    let mock = MockInstatus::start();
+    mock.add_metric("metric-1", "Gateway");
+
+    let server = common::start_tcp_server();
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({ "Gateway": { "url": server.to_string(), "type": "reachability" } }),
+    ));
+
+    mock.wait_for(TIMEOUT, "latency to be reported", |mock| {
+        !mock.metric_points().is_empty()
+    });
+
+    assert_eq!(mock.metric_points()[0].metric_id, "metric-1");
+    assert!(mock.incidents().is_empty());
+}
+
+#[test]
+fn heartbeat_monitors_open_incidents_when_pings_stop() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "Backups");
+
+    let velocity = common::spawn_velocity(common::config_with(
+        &mock,
+        json!({ "Backups": { "url": "/jobs/backups", "type": "heartbeat", "gracePeriod": 1 } }),
+        json!({ "heartbeatAddress": "127.0.0.1:0" }),
+    ));
+
+    mock.wait_for(TIMEOUT, "heartbeat listener to start", |_| {
+        velocity
+            .events()
+            .iter()
+            .any(|event| matches!(event, Event::HeartbeatListening { .. }))
+    });
+
+    let address = velocity
+        .events()
+        .iter()
+        .find_map(|event| match event {
+            Event::HeartbeatListening { address } => Some(*address),
+            _ => None,
+        })
+        .unwrap();
+
+    let ping = move |path: &str| {
+        smol::block_on(surf::post(format!("http://{}{}", address, path)))
+            .unwrap()
+            .status()
+    };
+
+    assert_eq!(ping("/jobs/backups/"), 200);
+    assert_eq!(ping("/jobs/other"), 404);
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    assert_eq!(mock.incidents()[0].name, "Backups Issues");
+
+    mock.wait_for(TIMEOUT, "incident to be monitored", |mock| {
+        ping("/jobs/backups");
+
+        mock.incidents()[0].status != "IDENTIFIED"
+    });
+
+    assert!(velocity
+        .events()
+        .iter()
+        .any(|event| matches!(event, Event::HeartbeatReceived { name } if name == "Backups")));
+}
+
+#[test]
+fn heartbeat_grace_period_defaults_to_the_config_frequency() {
+    // deserialized directly, so the monitor has no frequency of its own
+    let config: Config = serde_json::from_value(json!({
+        "name": common::PAGE_NAME,
+        "apiKey": common::API_KEY,
+        "monitors": { "Backups": { "url": "/jobs/backups", "type": "heartbeat" } },
+        "frequency": 60,
+    }))
+    .unwrap();
+
+    let heartbeats = Heartbeats::new(&config);
+
+    let result = heartbeats.check("Backups".to_string(), config.monitors["Backups"].clone());
+
+    assert_eq!(result.failure, None);
+}
+
+#[test]
+fn command_monitors_follow_exit_statuses() {
+    let mock = MockInstatus::start();
+
+    let config = common::config(
+        &mock,
+        json!({
+            "Ok": { "type": "command", "command": ["sh", "-c", "echo 'DISK OK - 42% free'"] },
+            "Warning": { "type": "command", "command": ["sh", "-c", "echo 'DISK WARNING - 15% free'; exit 1"] },
+            "Critical": { "type": "command", "command": ["sh", "-c", "echo 'DISK CRITICAL' >&2; exit 2"] },
+            "Matching": { "type": "command", "command": ["echo", "replication lag 3s"], "outputMatches": "lag [0-9]s" },
+            "NotMatching": { "type": "command", "command": ["echo", "replication lag 30s"], "outputMatches": "lag [0-9]s" },
+            "Missing": { "type": "command", "command": ["/nonexistent/check_disk"] },
+            "Slow": { "type": "command", "command": ["sleep", "5"], "timeout": 1 },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+    let result = |name: &str| results.iter().find(|result| result.name == name).unwrap();
+
+    assert_eq!(result("Ok").failure, None);
+    assert_eq!(result("Ok").detail.as_deref(), Some("DISK OK - 42% free"));
+    assert_eq!(result("Matching").failure, None);
+
+    assert_eq!(
+        result("Warning").failure.as_deref(),
+        Some("sh exited with status 1: DISK WARNING - 15% free")
+    );
+    assert_eq!(result("Warning").severity, Severity::Degraded);
+    assert_eq!(
+        result("Critical").failure.as_deref(),
+        Some("sh exited with status 2: DISK CRITICAL")
+    );
+    assert_eq!(result("Critical").severity, Severity::Outage);
+    assert_eq!(
+        result("NotMatching").failure.as_deref(),
+        Some("output does not match /lag [0-9]s/")
+    );
+    assert!(result("Missing")
+        .failure
+        .as_deref()
+        .unwrap()
+        .starts_with("failed to run /nonexistent/check_disk"));
+    assert_eq!(
+        result("Slow").failure.as_deref(),
+        Some("check timed out after 1s")
+    );
+}
+
+#[test]
+fn content_keywords_are_required() {
+    let mock = MockInstatus::start();
+    mock.set_response(200, &[], "<button>Add to cart</button>");
+
+    let config = common::config(
+        &mock,
+        json!({
+            "Shop": { "url": mock.custom_url(), "type": "uptime", "content": { "keywords": ["Add to cart"] } },
+            "Checkout": {
+                "url": mock.custom_url(),
+                "type": "uptime",
+                "content": { "keywords": ["Add to cart", "Checkout"], "severity": "degraded" },
+            },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+
+    assert_eq!(results[1].name, "Shop");
+    assert_eq!(results[1].failure, None);
+
+    assert_eq!(results[0].name, "Checkout");
+    assert_eq!(
+        results[0].failure.as_deref(),
+        Some("keyword \"Checkout\" is missing from the body")
+    );
+    assert_eq!(results[0].severity, Severity::Degraded);
+}
+
+#[test]
+fn content_changes_open_incidents() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "Landing");
+    mock.set_response(200, &[], "Welcome <input name=csrf value=1>");
+
+    let velocity = common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "Landing": {
+                "url": mock.custom_url(),
+                "type": "uptime",
+                "content": { "detectChanges": true, "ignore": "value=\\d+" },
+            },
+        }),
+    ));
+
+    let checks = || {
+        velocity
+            .events()
+            .iter()
+            .filter(|event| matches!(event, Event::Checked(_)))
+            .count()
+    };
+
+    mock.wait_for(TIMEOUT, "first check", |_| checks() >= 1);
+
+    // only the ignored token changes
+    mock.set_response(200, &[], "Welcome <input name=csrf value=2>");
+    mock.wait_for(TIMEOUT, "volatile change to be checked", |_| checks() >= 3);
+    assert!(mock.incidents().is_empty());
+
+    mock.set_response(200, &[], "Hacked <input name=csrf value=3>");
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    assert!(velocity.events().iter().any(|event| matches!(
+        event,
+        Event::Checked(result) if result.failure.as_deref() == Some("content changed since the last check")
+    )));
+}
+
+#[test]
+fn grpc_monitors_map_serving_statuses() {
+    let mock = MockInstatus::start();
+
+    let server = format!("http://{}", common::start_grpc_server());
+    let tls = common::start_grpc_tls_server();
+
+    let config = common::config(
+        &mock,
+        json!({
+            "Server": { "url": server, "type": "grpc" },
+            "Database": { "url": server, "type": "grpc", "service": "db" },
+            "Cache": { "url": server, "type": "grpc", "service": "cache" },
+            "Missing": { "url": server, "type": "grpc", "service": "missing" },
+            "Tls": {
+                "url": format!("https://localhost:{}", tls.addr.port()),
+                "type": "grpc",
+                "caCertificate": tls.ca_path,
+            },
+            "Closed": { "url": format!("http://{}", common::closed_addr()), "type": "grpc" },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+    let result = |name: &str| results.iter().find(|result| result.name == name).unwrap();
+
+    assert_eq!(result("Server").failure, None);
+    assert_eq!(result("Tls").failure, None);
+
+    assert_eq!(
+        result("Database").failure.as_deref(),
+        Some("db is NOT_SERVING")
+    );
+    assert_eq!(result("Database").severity, Severity::Outage);
+    assert_eq!(
+        result("Cache").failure.as_deref(),
+        Some("cache health is UNKNOWN")
+    );
+    assert_eq!(result("Cache").severity, Severity::Degraded);
+    assert_eq!(
+        result("Missing").failure.as_deref(),
+        Some("health check failed with status NOT_FOUND: unknown service")
+    );
+    assert!(result("Closed")
+        .failure
+        .as_deref()
+        .unwrap()
+        .starts_with("failed to connect to"));
+}
+
+#[test]
+fn websocket_monitors_wait_for_replies() {
+    let mock = MockInstatus::start();
+
+    let server = format!("ws://{}/realtime", common::start_websocket_server());
+    let tls = common::start_websocket_tls_server();
+
+    let config = common::config(
+        &mock,
+        json!({
+            "Handshake": { "url": server, "type": "websocket" },
+            "Echo": { "url": server, "type": "websocket", "probe": "ping", "banner": "ping" },
+            "Silent": {
+                "url": server,
+                "type": "websocket",
+                "probe": "ping",
+                "banner": "pong",
+                "timeout": 1,
+            },
+            "Tls": {
+                "url": format!("wss://localhost:{}/realtime", tls.addr.port()),
+                "type": "websocket",
+                "probe": "ping",
+                "banner": "ping",
+                "caCertificate": tls.ca_path,
+            },
+            "Http": { "url": format!("ws://{}/health", mock.addr()), "type": "websocket" },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+    let result = |name: &str| results.iter().find(|result| result.name == name).unwrap();
+
+    assert_eq!(result("Handshake").failure, None);
+    assert_eq!(result("Handshake").detail, None);
+
+    assert_eq!(result("Echo").failure, None);
+    assert!(result("Echo")
+        .detail
+        .as_deref()
+        .unwrap()
+        .starts_with("handshake "));
+
+    assert_eq!(result("Tls").failure, None);
+
+    assert_eq!(
+        result("Silent").failure.as_deref(),
+        Some("expected reply \"pong\", got \"ping\"")
+    );
+    assert!(result("Http")
+        .failure
+        .as_deref()
+        .unwrap()
+        .starts_with("WebSocket handshake failed"));
+}
+
+#[test]
+fn websocket_monitors_report_round_trip_to_metric() {
+    let mock = MockInstatus::start();
+    mock.add_metric("metric-1", "Realtime");
+
+    let server = format!("ws://{}", common::start_websocket_server());
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "Realtime": { "url": server, "type": "websocket", "probe": "ping", "banner": "ping" },
+        }),
+    ));
+
+    mock.wait_for(TIMEOUT, "latency to be reported", |mock| {
+        !mock.metric_points().is_empty()
+    });
+
+    assert_eq!(mock.metric_points()[0].metric_id, "metric-1");
+    assert!(mock.incidents().is_empty());
+}
+
+#[test]
+fn flow_monitors_pass_extracted_values_between_steps() {
+    let mock = MockInstatus::start();
+    mock.set_response(
+        200,
+        &[("X-Session", "s3ss10n")],
+        r#"{ "token": "t0k3n", "user": { "id": 7 }, "form": "csrf=abc123" }"#,
+    );
+    mock.set_healthy(false);
+
+    let login = json!({
+        "name": "login",
+        "url": mock.custom_url(),
+        "method": "POST",
+        "extract": {
+            "token": { "json": "$.token" },
+            "user": { "json": "$.user.id" },
+            "session": { "header": "X-Session" },
+            "csrf": { "regex": "csrf=(\\w+)" },
+        },
+    });
+
+    let config = common::config(
+        &mock,
+        json!({
+            "Checkout": {
+                "type": "flow",
+                "headers": { "X-Client": "velocity" },
+                "steps": [
+                    login,
+                    {
+                        "url": mock.echo_url(),
+                        "method": "POST",
+                        "headers": { "Authorization": "Bearer {{token}}", "X-Session": "{{ session }}" },
+                        "body": { "json": { "user": "{{user}}", "csrf": "{{csrf}}" } },
+                    },
+                ],
+            },
+            "Profile": {
+                "type": "flow",
+                "steps": [login, { "name": "profile", "url": mock.health_url() }],
+            },
+            "Missing": {
+                "type": "flow",
+                "steps": [{ "url": mock.custom_url(), "extract": { "id": { "json": "$.id" } } }],
+            },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+    let result = |name: &str| results.iter().find(|result| result.name == name).unwrap();
+
+    assert_eq!(result("Checkout").failure, None);
+    assert_eq!(result("Checkout").step, None);
+
+    let echoed = &mock.echoed()[0];
+    assert_eq!(echoed.header("Authorization"), Some("Bearer t0k3n"));
+    assert_eq!(echoed.header("X-Session"), Some("s3ss10n"));
+    assert_eq!(echoed.header("X-Client"), Some("velocity"));
+    assert_eq!(echoed.body, r#"{"csrf":"abc123","user":"7"}"#);
+
+    assert_eq!(
+        result("Profile").failure.as_deref(),
+        Some("step 2 (profile) failed: unexpected status 503")
+    );
+    assert_eq!(result("Profile").step.as_deref(), Some("step 2 (profile)"));
+
+    assert_eq!(
+        result("Missing").failure.as_deref(),
+        Some("step 1 failed: failed to extract id: $.id is missing from the body")
+    );
+}
+
+#[test]
+fn flow_incidents_name_the_failed_step() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "Checkout");
+    mock.set_response(200, &[], r#"{ "token": "t0k3n" }"#);
+    mock.set_healthy(false);
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "Checkout": {
+                "type": "flow",
+                "steps": [
+                    { "name": "login", "url": mock.custom_url(), "extract": { "token": { "json": "token" } } },
+                    { "name": "cart", "url": mock.health_url(), "headers": { "X-Token": "{{token}}" } },
+                ],
+            },
+        }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    assert_eq!(
+        mock.incidents()[0].message,
+        "We've identified issues with the Checkout, step 2 (cart) failed. Engineers have been notified."
+    );
+}
+
+#[test]
+fn incidents_wait_for_confirmed_failures_and_recoveries() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.set_healthy(false);
+
+    let velocity = common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "API": {
+                "url": mock.health_url(),
+                "type": "uptime",
+                "failuresBeforeIncident": 3,
+                "successesBeforeRecovery": 2,
+            },
+        }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    mock.set_healthy(true);
+
+    mock.wait_for(TIMEOUT, "incident to be resolved", |mock| {
+        mock.incidents()[0].status == "RESOLVED"
+    });
+
+    let events = velocity.events();
+
+    let position = |predicate: &dyn Fn(&Event) -> bool| events.iter().position(predicate).unwrap();
+    let opened = position(&|event| matches!(event, Event::IncidentOpened { .. }));
+    let monitoring = position(&|event| matches!(event, Event::IncidentMonitoring { .. }));
+
+    let checks = |range: std::ops::Range<usize>, success: bool| {
+        events[range]
+            .iter()
+            .filter(
+                |event| matches!(event, Event::Checked(result) if result.is_success() == success),
+            )
+            .count()
+    };
+
+    assert_eq!(checks(0..opened, false), 3);
+    assert_eq!(checks(opened..monitoring, true), 2);
+}
+
+#[test]
+fn flapping_monitors_do_not_recover_with_other_monitors() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.add_component("component-2", "Website");
+    mock.set_healthy(false);
+    mock.set_response(200, &[], "");
+
+    let velocity = common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "API": {
+                "url": mock.health_url(),
+                "type": "uptime",
+                "flapDetection": { "changes": 1, "checks": 4 },
+            },
+            "Website": { "url": mock.custom_url(), "type": "uptime" },
+        }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    // the first success counts as a change, so the monitor flaps until it has been up for 4 checks
+    mock.set_healthy(true);
+
+    mock.wait_for(TIMEOUT, "incident to be resolved", |mock| {
+        mock.incidents()[0].status == "RESOLVED"
+    });
+
+    let events = velocity.events();
+
+    let position = |predicate: &dyn Fn(&Event) -> bool| events.iter().position(predicate).unwrap();
+    let started =
+        position(&|event| matches!(event, Event::FlappingStarted { name, .. } if name == "API"));
+    let stopped =
+        position(&|event| matches!(event, Event::FlappingStopped { name } if name == "API"));
+    let monitoring = position(&|event| matches!(event, Event::IncidentMonitoring { .. }));
+
+    // the website kept succeeding while the API was flapping, without moving its incident along
+    assert!(started < stopped);
+    assert!(stopped < monitoring);
+    assert!(matches!(&events[monitoring], Event::IncidentMonitoring { name, .. } if name == "API"));
+    assert_eq!(mock.incidents().len(), 1);
+}
+
+#[test]
+fn failed_checks_are_retried_with_backoff() {
+    let mock = MockInstatus::start();
+    mock.fail_health_checks(2);
+
+    let config = common::config(
+        &mock,
+        json!({
+            "API": { "url": mock.health_url(), "type": "uptime", "retries": 3, "retryDelay": 10 },
+            "Closed": {
+                "url": common::closed_addr().to_string(),
+                "type": "tcp",
+                "retries": 2,
+                "retryDelay": 10,
+                "retryTimeout": 1,
+            },
+        }),
+    );
+
+    let results = smol::block_on(check_all(&net::build_client(None).unwrap(), &config));
+    let result = |name: &str| results.iter().find(|result| result.name == name).unwrap();
+
+    assert_eq!(result("API").failure, None);
+    assert_eq!(result("API").attempts, 3);
+
+    assert!(result("Closed").failure.is_some());
+    assert_eq!(result("Closed").attempts, 3);
+}
+
+#[test]
+fn retried_checks_do_not_open_incidents() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.fail_health_checks(1);
+
+    let velocity = common::spawn_velocity(common::config(
+        &mock,
+        json!({ "API": { "url": mock.health_url(), "type": "uptime", "retries": 1, "retryDelay": 10 } }),
+    ));
+
+    mock.wait_for(TIMEOUT, "check to complete", |_| {
+        velocity
+            .events()
+            .iter()
+            .any(|event| matches!(event, Event::Checked(_)))
+    });
+
+    let summary = velocity.stop();
+
+    assert_eq!(summary.failures, 0);
+    assert!(mock.incidents().is_empty());
+}
+
+#[test]
+fn sustained_latency_opens_degraded_incidents() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.add_metric("metric-1", "API");
+
+    let velocity = common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "API": {
+                "url": mock.slow_url(200),
+                "type": "latency",
+                "latencyThreshold": { "warning": 50, "window": 2 },
+            },
+        }),
+    ));
+
+    mock.wait_for(TIMEOUT, "incident to be identified", |mock| {
+        !mock.incidents().is_empty()
+    });
+
+    let incident = &mock.incidents()[0];
+    assert_eq!(
+        incident.statuses,
+        vec![("component-1".to_string(), "DEGRADEDPERFORMANCE".to_string())]
+    );
+    assert_eq!(
+        incident.message,
+        "We've identified increased response times of the API. Engineers have been notified."
+    );
+
+    let checks: Vec<_> = velocity
+        .events()
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::Checked(result) => Some(result),
+            _ => None,
+        })
+        .collect();
+
+    // the first slow check is within the window
+    assert!(checks[0].is_success());
+    assert!(checks[1]
+        .failure
+        .as_deref()
+        .unwrap()
+        .ends_with("above the warning threshold of 50 ms for the last 2 checks"));
+
+    // slow checks still report their latency
+    assert!(mock.metric_points().len() >= 2);
+}
+
+#[test]
+fn shared_components_escalate_from_partial_to_major_outage() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.set_response(503, &[], "");
+
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "API (eu)": { "url": mock.health_url(), "type": "uptime", "components": ["API"] },
+            "API (us)": { "url": mock.custom_url(), "type": "uptime", "components": ["API"] },
+        }),
+    ));
+
+    let status = |mock: &MockInstatus| {
+        mock.incidents()
+            .first()
+            .map(|incident| incident.statuses[0].1.clone())
+    };
+
+    mock.wait_for(TIMEOUT, "partial outage", |mock| {
+        status(mock).as_deref() == Some("PARTIALOUTAGE")
+    });
+
+    assert_eq!(mock.incidents()[0].name, "API (us) Issues");
+    assert_eq!(
+        mock.incidents()[0].components,
+        vec!["component-1".to_string()]
+    );
+
+    mock.set_healthy(false);
+    mock.wait_for(TIMEOUT, "escalation to a major outage", |mock| {
+        status(mock).as_deref() == Some("MAJOROUTAGE")
+    });
+
+    mock.set_healthy(true);
+    mock.wait_for(TIMEOUT, "de-escalation to a partial outage", |mock| {
+        status(mock).as_deref() == Some("PARTIALOUTAGE")
+    });
+
+    // still identified while one of the monitors is down
+    assert_eq!(mock.incidents()[0].status, "IDENTIFIED");
+
+    mock.set_response(200, &[], "");
+    mock.wait_for(TIMEOUT, "incident to be resolved", |mock| {
+        mock.incidents()[0].status == "RESOLVED"
+    });
+
+    // escalated and de-escalated without opening another incident
+    assert_eq!(mock.incidents().len(), 1);
+    assert_eq!(
+        mock.incidents()[0].updates[..2],
+        ["IDENTIFIED", "IDENTIFIED"]
+    );
+}
+
+#[test]
+fn shared_components_failing_together_open_a_single_incident() {
+    let mock = MockInstatus::start();
+    mock.add_component("component-1", "API");
+    mock.set_healthy(false);
+
+    // both monitors fail in the same batch of checks
+    common::spawn_velocity(common::config(
+        &mock,
+        json!({
+            "API (eu)": { "url": mock.health_url(), "type": "uptime", "components": ["API"] },
+            "API (us)": { "url": mock.health_url(), "type": "uptime", "components": ["API"] },
+        }),
+    ));
+
+    mock.wait_for(TIMEOUT, "escalation to a major outage", |mock| {
+        mock.incidents()
+            .first()
+            .is_some_and(|incident| incident.statuses[0].1 == "MAJOROUTAGE")
+    });
+
+    std::thread::sleep(Duration::from_secs(2));
+
+    assert_eq!(mock.incidents().len(), 1);
+}
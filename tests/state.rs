@@ -0,0 +1,146 @@
+use serde_json::json;
+use velocity::{
+    check::Severity,
+    state::{Flap, MonitorState},
+    CheckResult, Monitor,
+};
+
+fn monitor(value: serde_json::Value) -> Monitor {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn failures_and_recoveries_are_confirmed_after_thresholds() {
+    let monitor = monitor(json!({
+        "url": "https://example.com",
+        "type": "uptime",
+        "failuresBeforeIncident": 3,
+        "successesBeforeRecovery": 2,
+    }));
+
+    let mut state = MonitorState::default();
+
+    for _ in 0..2 {
+        assert_eq!(state.record(&monitor, false), None);
+        assert!(!state.failure_confirmed(&monitor));
+    }
+
+    state.record(&monitor, false);
+    assert!(state.failure_confirmed(&monitor));
+
+    state.record(&monitor, true);
+    assert!(!state.recovery_confirmed(&monitor));
+    assert!(!state.failure_confirmed(&monitor));
+
+    state.record(&monitor, true);
+    assert!(state.recovery_confirmed(&monitor));
+}
+
+#[test]
+fn thresholds_default_to_a_single_check() {
+    let monitor = monitor(json!({ "url": "https://example.com", "type": "uptime" }));
+
+    let mut state = MonitorState::default();
+
+    state.record(&monitor, false);
+    assert!(state.failure_confirmed(&monitor));
+
+    state.record(&monitor, true);
+    assert!(state.recovery_confirmed(&monitor));
+}
+
+#[test]
+fn flapping_holds_back_incidents_until_stable() {
+    let monitor = monitor(json!({
+        "url": "https://example.com",
+        "type": "uptime",
+        "flapDetection": { "changes": 3, "checks": 5 },
+    }));
+
+    let mut state = MonitorState::default();
+
+    assert_eq!(state.record(&monitor, false), None);
+    assert_eq!(state.record(&monitor, true), None);
+    assert_eq!(state.record(&monitor, false), None);
+    assert_eq!(
+        state.record(&monitor, true),
+        Some(Flap::Started { changes: 3 })
+    );
+
+    assert!(state.is_flapping());
+    assert!(!state.recovery_confirmed(&monitor));
+
+    assert_eq!(state.record(&monitor, false), None);
+    assert!(!state.failure_confirmed(&monitor));
+
+    // stable once the last 5 checks all failed
+    for _ in 0..3 {
+        assert_eq!(state.record(&monitor, false), None);
+    }
+
+    assert_eq!(state.record(&monitor, false), Some(Flap::Stopped));
+    assert!(state.failure_confirmed(&monitor));
+}
+
+#[test]
+fn latency_above_the_threshold_is_degraded_once_sustained() {
+    let monitor = monitor(json!({
+        "url": "https://example.com",
+        "type": "latency",
+        "latencyThreshold": { "warning": 100, "window": 2 },
+    }));
+
+    let result = |latency: u128, failure: Option<&str>| CheckResult {
+        name: "API".to_string(),
+        monitor: monitor.clone(),
+        latency,
+        failure: failure.map(str::to_string),
+        severity: Severity::Outage,
+        detail: None,
+        content_hash: None,
+        step: None,
+        attempts: 1,
+    };
+
+    let mut state = MonitorState::default();
+
+    assert!(state.record_latency(&result(150, None)).is_none());
+
+    let failure = state.record_latency(&result(180, None)).unwrap();
+    assert_eq!(failure.severity, Severity::Degraded);
+    assert_eq!(
+        failure.reason,
+        "latency of 180 ms is above the warning threshold of 100 ms for the last 2 checks"
+    );
+
+    // recovers as soon as a check is fast again, and failed checks don't count as slow
+    assert!(state.record_latency(&result(50, None)).is_none());
+    assert!(state.record_latency(&result(150, None)).is_none());
+    assert!(state
+        .record_latency(&result(30000, Some("check timed out after 30s")))
+        .is_none());
+    assert!(state.record_latency(&result(150, None)).is_none());
+}
+
+#[test]
+fn component_status_depends_on_the_share_of_failing_monitors() {
+    use velocity::state::component_status;
+
+    assert_eq!(component_status([None, None]), None);
+    assert_eq!(
+        component_status([Some(Severity::Outage)]),
+        Some("MAJOROUTAGE")
+    );
+    assert_eq!(
+        component_status([Some(Severity::Outage), None]),
+        Some("PARTIALOUTAGE")
+    );
+    assert_eq!(
+        component_status([Some(Severity::Outage), Some(Severity::Degraded)]),
+        Some("PARTIALOUTAGE")
+    );
+    assert_eq!(
+        component_status([Some(Severity::Degraded), None]),
+        Some("DEGRADEDPERFORMANCE")
+    );
+}